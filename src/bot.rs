@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::eval::Weights;
+use crate::rng::Rng;
+use crate::{Map, Moves, Player};
+
+/// Settings shared by bots created from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct BotOptions {
+    pub weights: Weights,
+    pub think: Option<Duration>,
+    pub playouts: Option<usize>,
+    pub iterations: Option<usize>,
+    pub threads: Option<usize>,
+}
+
+/// Computer opponent choosing moves for its player.
+pub trait Bot {
+    /// Choose up to `mov_num` moves for `player` on the given map.
+    fn choose_moves(&mut self, map: &Map, player: &Player, mov_num: usize) -> Moves;
+}
+
+/// Bot submitting random legal moves, the baseline for everything else.
+#[derive(Debug, Clone)]
+pub struct RandomBot {
+    rng: Rng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn choose_moves(&mut self, map: &Map, player: &Player, mov_num: usize) -> Moves {
+        let mut map = map.clone();
+        let mut moves: Moves = [None, None, None];
+        for slot in moves.iter_mut().take(mov_num) {
+            let candidates = map.legal_moves_for(player);
+            if let Some((e, t)) = self.rng.choose(&candidates).cloned() {
+                map.move_entity(e.position, t);
+                *slot = Some((e, t));
+            }
+        }
+        moves
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::testing::players;
+
+    #[test]
+    fn test_random_moves_are_legal() {
+        let [p1, p2] = players();
+        let map = Map::init(p1.clone(), p2);
+        let mut bot = RandomBot::new(7);
+        for _ in 0..20 {
+            let mut scratch = map.clone();
+            for (e, t) in bot.choose_moves(&map, &p1, 3).iter().flatten() {
+                assert_eq!(e.owner_id, p1.id);
+                assert!(scratch.is_legal(e, *t));
+                scratch.move_entity(e.position, *t);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_bot_is_reproducible() {
+        let [p1, p2] = players();
+        let map = Map::init(p1, p2.clone());
+        let mut a = RandomBot::new(42);
+        let mut b = RandomBot::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.choose_moves(&map, &p2, 2), b.choose_moves(&map, &p2, 2));
+        }
+    }
+
+    #[test]
+    fn test_no_moves_without_entities() {
+        let [p1, _] = players();
+        let mut bot = RandomBot::new(1);
+        assert_eq!(bot.choose_moves(&Map::empty(), &p1, 3), [None, None, None]);
+    }
+}
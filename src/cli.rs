@@ -0,0 +1,570 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::bot::BotOptions;
+use crate::clock::{FlagPolicy, TimeControl};
+use crate::editor::Editor;
+use crate::eval::Weights;
+use crate::events::SessionEvent;
+use crate::export::{board_svg, game_html};
+use crate::figures::{by_name, load_figure, standard_set};
+use crate::fog::Vision;
+use crate::inspect;
+use crate::record::GameRecord;
+use crate::render::Renderer;
+use crate::rules::Rules;
+use crate::scenario::{Outcome, Scenario};
+use crate::server::{self, Server, TimeoutPolicy};
+use crate::solver::{ShowLine, Solver, Verdict};
+use crate::tournament::Tournament;
+use crate::tui::Tui;
+use crate::{Controller, Map, Player, Position, Session};
+
+const USAGE: &str = "Usage: not_so_chess [COMMAND] [OPTIONS]
+Commands:
+  play              play a game in the terminal (default)
+  tournament        play bots against each other without output
+  replay FILE       show a recorded game round by round
+  export FILE       write a recorded game to --out, a page with every round
+                    for .html, the board after --round for .svg
+  puzzle FILE       play a scenario file, its objective within its round limit
+  solve FILE        search a scenario file for moves forcing its objective
+                    against every reply, or a reply refuting every try
+  edit              build a board on an empty map and save it as a scenario
+  inspect FIGURE    show a built-in figure or figure file, its pattern, the
+                    squares it threatens --at a square and pattern warnings
+  serve ADDR        host a lobby of network games, e.g. 127.0.0.1:7878
+  connect ADDR      join a game on a server, lines typed are sent as they are
+Options:
+  --p1 KIND         first player, KIND: human | random[:SEED] | matrix[:SEED] | mcts[:SEED]
+  --p2 KIND         second player
+  --moves N         moves per player and round, 1 to 3
+  --weights FILE    evaluation weights for bots, prints evaluation after every round
+  --think MS        time limit of search bots per round in milliseconds
+  --playouts N      playouts of the mcts bot per round
+  --iterations N    regret matching iterations of the matrix bot per round
+  --threads N       threads of the mcts bot
+  --games N         games of the tournament
+  --nodes N         rounds the solver resolves before giving up, 10000 by default
+  --round-time MS   time each player has per round of a played game
+  --bank MS         time each player has for the whole played game
+  --increment MS    time added to the bank after every round
+  --on-flag WHAT    what a player out of time loses, WHAT: moves | game
+  --at 'X Y'        square to inspect a figure on, its start by default
+  --board FILE      game record whose board after --round a figure is inspected on
+  --round N         round to export, the last one by default
+  --out PATH        directory to write tournament game records to, file to export to
+  --tui             humans enter moves on a full-screen board
+  --fog VISION      fog of war for played and served games, VISION: pattern | radius:N
+  --commit-reveal   served games take moves by COMMIT and REVEAL
+  --delay N         spectators of served games see rounds N rounds late
+  --timeout MS      time players of served games have to submit each round
+  --on-timeout WHAT what a late player loses, WHAT: moves | game
+  --grace MS        time served games wait for a player to reconnect before
+                    their round timeouts count, a minute by default";
+
+enum Command {
+    Play,
+    Tournament,
+    Replay(PathBuf),
+    Export(PathBuf),
+    Puzzle(PathBuf),
+    Solve(PathBuf),
+    Edit,
+    Inspect(String),
+    Serve(String),
+    Connect(String),
+}
+
+struct Options {
+    kinds: [String; 2],
+    bot: BotOptions,
+    analyse: bool,
+    mov_num: usize,
+    games: u32,
+    node_limit: Option<usize>,
+    out_dir: Option<PathBuf>,
+    round: Option<usize>,
+    at: Option<Position>,
+    board: Option<PathBuf>,
+    tui: bool,
+    fog: Option<Vision>,
+    commit_reveal: bool,
+    spectator_delay: usize,
+    round_timeout: Option<Duration>,
+    on_timeout: TimeoutPolicy,
+    reconnect_grace: Option<Duration>,
+    time_control: TimeControl,
+    bank: Option<Duration>,
+    increment: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            kinds: ["human".to_string(), "human".to_string()],
+            bot: BotOptions::default(),
+            analyse: false,
+            mov_num: 1,
+            games: 10,
+            node_limit: None,
+            out_dir: None,
+            round: None,
+            at: None,
+            board: None,
+            tui: false,
+            fog: None,
+            commit_reveal: false,
+            spectator_delay: 0,
+            round_timeout: None,
+            on_timeout: TimeoutPolicy::ForfeitMoves,
+            reconnect_grace: None,
+            time_control: TimeControl::default(),
+            bank: None,
+            increment: Duration::from_secs(0),
+        }
+    }
+}
+
+/// Run the command described by command line arguments.
+pub fn run<I: Iterator<Item = String>>(args: I) {
+    let result = parse(args).and_then(|(command, options)| match command {
+        Command::Play => play(options),
+        Command::Tournament => tournament(options),
+        Command::Replay(path) => replay(path),
+        Command::Export(path) => export(path, options),
+        Command::Puzzle(path) => puzzle(path, options),
+        Command::Solve(path) => solve(path, options),
+        Command::Edit => {
+            let stdin = io::stdin();
+            Editor::new(Renderer::for_stdout())
+                .run(stdin.lock(), &mut io::stdout())
+                .map_err(|e| format!("Editor stopped: {}", e))
+        }
+        Command::Inspect(figure) => inspect(figure, options),
+        Command::Serve(addr) => serve(addr, options),
+        Command::Connect(addr) => {
+            server::connect(&addr).map_err(|e| format!("Could not connect to {}: {}", addr, e))
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("{}\n{}", e, USAGE);
+    }
+}
+
+fn parse<I: Iterator<Item = String>>(args: I) -> Result<(Command, Options), String> {
+    let mut args = args.peekable();
+    let command = match args.peek().map(String::as_str) {
+        Some("play") => {
+            args.next();
+            Command::Play
+        }
+        Some("tournament") => {
+            args.next();
+            Command::Tournament
+        }
+        Some("replay") => {
+            args.next();
+            match args.next() {
+                Some(path) => Command::Replay(PathBuf::from(path)),
+                None => return Err("Expected a record file after replay".to_string()),
+            }
+        }
+        Some("export") => {
+            args.next();
+            match args.next() {
+                Some(path) => Command::Export(PathBuf::from(path)),
+                None => return Err("Expected a record file after export".to_string()),
+            }
+        }
+        Some("puzzle") => {
+            args.next();
+            match args.next() {
+                Some(path) => Command::Puzzle(PathBuf::from(path)),
+                None => return Err("Expected a scenario file after puzzle".to_string()),
+            }
+        }
+        Some("solve") => {
+            args.next();
+            match args.next() {
+                Some(path) => Command::Solve(PathBuf::from(path)),
+                None => return Err("Expected a scenario file after solve".to_string()),
+            }
+        }
+        Some("edit") => {
+            args.next();
+            Command::Edit
+        }
+        Some("inspect") => {
+            args.next();
+            match args.next() {
+                Some(figure) => Command::Inspect(figure),
+                None => return Err("Expected a figure name or file after inspect".to_string()),
+            }
+        }
+        Some("serve") => {
+            args.next();
+            match args.next() {
+                Some(addr) => Command::Serve(addr),
+                None => return Err("Expected an address after serve".to_string()),
+            }
+        }
+        Some("connect") => {
+            args.next();
+            match args.next() {
+                Some(addr) => Command::Connect(addr),
+                None => return Err("Expected an address after connect".to_string()),
+            }
+        }
+        _ => Command::Play,
+    };
+
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if arg == "--commit-reveal" {
+            options.commit_reveal = true;
+            continue;
+        }
+        if arg == "--tui" {
+            options.tui = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Expected a value after {}", arg))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid number {}: {}", value, e))
+        };
+        match arg.as_str() {
+            "--p1" => options.kinds[0] = value.clone(),
+            "--p2" => options.kinds[1] = value.clone(),
+            "--moves" => match number()? {
+                n @ 1..=3 => options.mov_num = n,
+                n => return Err(format!("Invalid number of moves {}", n)),
+            },
+            "--weights" => {
+                options.bot.weights = Weights::load(&value)
+                    .map_err(|e| format!("Could not load weights from {}: {}", value, e))?;
+                options.analyse = true;
+            }
+            "--think" => options.bot.think = Some(Duration::from_millis(number()? as u64)),
+            "--playouts" => options.bot.playouts = Some(number()?),
+            "--iterations" => options.bot.iterations = Some(number()?),
+            "--threads" => options.bot.threads = Some(number()?),
+            "--games" => options.games = number()? as u32,
+            "--nodes" => options.node_limit = Some(number()?),
+            "--delay" => options.spectator_delay = number()?,
+            "--timeout" => options.round_timeout = Some(Duration::from_millis(number()? as u64)),
+            "--grace" => options.reconnect_grace = Some(Duration::from_millis(number()? as u64)),
+            "--on-timeout" => match value.as_str() {
+                "moves" => options.on_timeout = TimeoutPolicy::ForfeitMoves,
+                "game" => options.on_timeout = TimeoutPolicy::ForfeitGame,
+                _ => return Err(format!("Invalid timeout policy {}", value)),
+            },
+            "--round-time" => {
+                let limit = Duration::from_millis(number()? as u64);
+                options.time_control = options.time_control.with_round_limit(limit);
+            }
+            "--bank" => options.bank = Some(Duration::from_millis(number()? as u64)),
+            "--increment" => options.increment = Duration::from_millis(number()? as u64),
+            "--on-flag" => match value.as_str() {
+                "moves" => {
+                    options.time_control = options
+                        .time_control
+                        .with_flag_policy(FlagPolicy::EmptyMoves)
+                }
+                "game" => {
+                    options.time_control = options.time_control.with_flag_policy(FlagPolicy::Loss)
+                }
+                _ => return Err(format!("Invalid flag policy {}", value)),
+            },
+            "--at" => {
+                let at: Position = value
+                    .parse()
+                    .map_err(|_| format!("Invalid square {}, expected \"x y\"", value))?;
+                if !at.is_valid() {
+                    return Err(format!("Square {} is off the board", at));
+                }
+                options.at = Some(at);
+            }
+            "--fog" => options.fog = Some(value.parse()?),
+            "--board" => options.board = Some(PathBuf::from(&value)),
+            "--round" => options.round = Some(number()?),
+            "--out" => options.out_dir = Some(PathBuf::from(&value)),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    match options.bank {
+        Some(bank) => {
+            options.time_control = options.time_control.with_bank(bank, options.increment)
+        }
+        None if !options.increment.is_zero() => {
+            return Err("--increment is added to the time bank, give one with --bank".to_string())
+        }
+        None => {}
+    }
+    Ok((command, options))
+}
+
+fn default_players() -> [Player; 2] {
+    [
+        Player::new("Ame".to_string(), 1, standard_set()),
+        Player::new("Gura".to_string(), 2, standard_set()),
+    ]
+}
+
+fn play(options: Options) -> Result<(), String> {
+    let mut controllers = [
+        Controller::new(&options.kinds[0], &options.bot)?,
+        Controller::new(&options.kinds[1], &options.bot)?,
+    ];
+    let rules = Rules::default()
+        .with_moves_per_round(options.mov_num)
+        .with_fog(options.fog);
+    let mut session = Session::new(default_players())
+        .with_rules(rules)
+        .with_time_control(options.time_control);
+    session
+        .check_symbols()
+        .map_err(|e| format!("Invalid figure set: {}", e))?;
+    attach_ui(&mut session, &mut controllers, options.tui);
+    session.show();
+    while session.is_active() {
+        session.calculate_round(&mut controllers);
+        if options.analyse {
+            for player in session.players().iter() {
+                let score = options
+                    .bot
+                    .weights
+                    .evaluate(&session.get_last_map(), player);
+                println!("Evaluation for {}: {:.2}", player.name(), score);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print what happens in `session`, or log it next to the board for humans
+/// entering moves on a full-screen board with `tui`. Under fog of war what
+/// happens during rounds is left out.
+fn attach_ui(session: &mut Session, controllers: &mut [Controller; 2], tui: bool) {
+    let fog = session.rules.fog().is_some();
+    if tui {
+        let log = Arc::new(Mutex::new(vec![]));
+        for controller in controllers.iter_mut() {
+            if let Controller::Human = controller {
+                *controller = Controller::Tui(Tui::new(log.clone()));
+            }
+        }
+        session.subscribe(Box::new(move |event: &SessionEvent| match event {
+            SessionEvent::MoveSubmitted { .. } => {}
+            SessionEvent::Round(_) if fog => {}
+            event => log.lock().unwrap().push(event.to_string()),
+        }));
+    } else {
+        session.subscribe(Box::new(move |event: &SessionEvent| match event {
+            SessionEvent::MoveSubmitted { .. } | SessionEvent::RoundEnded { .. } => {}
+            SessionEvent::Round(_) if fog => {}
+            event => println!("{}", event),
+        }));
+    }
+}
+
+/// Load a scenario file, refusing one that can not be played.
+fn playable(path: &Path) -> Result<Scenario, String> {
+    let scenario =
+        Scenario::load(path).map_err(|e| format!("Could not load {}: {}", path.display(), e))?;
+    let problems = scenario.problems();
+    if !problems.is_empty() {
+        return Err(format!(
+            "Cannot play {}, {}",
+            path.display(),
+            problems.join(", ")
+        ));
+    }
+    Ok(scenario)
+}
+
+fn puzzle(path: PathBuf, options: Options) -> Result<(), String> {
+    let scenario = playable(&path)?;
+    let mut controllers = [
+        Controller::new(&options.kinds[0], &options.bot)?,
+        Controller::new(&options.kinds[1], &options.bot)?,
+    ];
+    let mut session = scenario.session().with_time_control(options.time_control);
+    attach_ui(&mut session, &mut controllers, options.tui);
+    println!(
+        "{}: {} must {} within {} rounds",
+        scenario.name, scenario.names[scenario.player], scenario.objective, scenario.rounds
+    );
+    session.show();
+    let mut outcome = scenario.outcome(session.record());
+    while outcome == Outcome::Pending && session.is_active() {
+        session.calculate_round(&mut controllers);
+        outcome = scenario.outcome(session.record());
+    }
+    match outcome {
+        Outcome::Solved => println!("Solved in {} rounds", session.record().len() - 1),
+        _ => println!("Failed"),
+    }
+    Ok(())
+}
+
+fn solve(path: PathBuf, options: Options) -> Result<(), String> {
+    let scenario = playable(&path)?;
+    let mut solver = Solver::new(&scenario);
+    if let Some(limit) = options.node_limit {
+        solver = solver.with_node_limit(limit);
+    }
+    let player = &scenario.names[scenario.player];
+    match solver.solve() {
+        Verdict::Forced(line) => print!(
+            "{} forces {} within {} rounds:\n{}",
+            player,
+            scenario.objective,
+            scenario.rounds,
+            ShowLine(&line)
+        ),
+        Verdict::Refuted(line) => print!(
+            "{} cannot force {}, a refutation of the longest try:\n{}",
+            player,
+            scenario.objective,
+            ShowLine(&line)
+        ),
+        Verdict::Unknown => {
+            println!("Unknown, the node limit was reached, allow more rounds with --nodes N")
+        }
+    }
+    println!("{} rounds resolved", solver.nodes());
+    Ok(())
+}
+
+fn tournament(options: Options) -> Result<(), String> {
+    let mut bots = vec![];
+    for kind in options.kinds.iter() {
+        match Controller::new(kind, &options.bot)? {
+            Controller::Bot(bot) => bots.push(bot),
+            _ => return Err("Tournaments are played by bots only".to_string()),
+        }
+    }
+    let second = bots.pop().unwrap();
+    let first = bots.pop().unwrap();
+    let names = [
+        format!("{} (p1)", options.kinds[0]),
+        format!("{} (p2)", options.kinds[1]),
+    ];
+    let mut tournament = Tournament::new(names, [first, second]).with_mov_num(options.mov_num);
+    if let Some(dir) = options.out_dir {
+        tournament = tournament.with_out_dir(dir);
+    }
+    let report = tournament
+        .run(options.games)
+        .map_err(|e| format!("Could not write game records: {}", e))?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// Load a game record with the standard set for both of its players.
+fn load_record(path: &Path) -> Result<(GameRecord, [Player; 2]), String> {
+    let record =
+        GameRecord::load(path).map_err(|e| format!("Could not load {}: {}", path.display(), e))?;
+    let [n1, n2] = record.names().clone();
+    let players = [
+        Player::new(n1, 1, standard_set()),
+        Player::new(n2, 2, standard_set()),
+    ];
+    Ok((record, players))
+}
+
+fn replay(path: PathBuf) -> Result<(), String> {
+    let (record, players) = load_record(&path)?;
+    let session = record.replay(players, |session| {
+        println!("Round {}", session.record().len() - 1);
+        session.show();
+    });
+    println!("{:?}", session.result());
+    println!("Recorded result: {}", record.result());
+    Ok(())
+}
+
+fn export(path: PathBuf, options: Options) -> Result<(), String> {
+    let (record, players) = load_record(&path)?;
+    let out = options
+        .out_dir
+        .ok_or_else(|| "Expected --out FILE to export to".to_string())?;
+    let content = match out.extension().and_then(|e| e.to_str()) {
+        Some("html") => game_html(&record, players),
+        Some("svg") => {
+            let session = record.replay(players, |_| {});
+            let maps = session.record();
+            let round = options.round.unwrap_or(maps.len()).min(maps.len() - 1);
+            let moves = match round {
+                0 => vec![],
+                n => record.rounds()[n - 1].concat(),
+            };
+            board_svg(&maps[round], record.names(), &moves)
+        }
+        _ => {
+            return Err(format!(
+                "Cannot export to {}, use .svg or .html",
+                out.display()
+            ))
+        }
+    };
+    fs::write(&out, content).map_err(|e| format!("Could not write {}: {}", out.display(), e))?;
+    println!("Wrote {}", out.display());
+    Ok(())
+}
+
+fn inspect(figure: String, options: Options) -> Result<(), String> {
+    let figure = match by_name(&figure) {
+        Some(figure) => figure,
+        None => load_figure(&figure).map_err(|e| format!("Could not load {}: {}", figure, e))?,
+    };
+    let board = match options.board {
+        Some(path) => {
+            let (record, players) = load_record(&path)?;
+            let session = record.replay(players, |_| {});
+            let maps = session.record();
+            maps[options.round.unwrap_or(maps.len()).min(maps.len() - 1)].clone()
+        }
+        None => Map::empty(),
+    };
+    let at = options.at.unwrap_or_else(|| figure.start_position());
+    println!("{}", inspect::inspect(&figure, at, &board));
+    Ok(())
+}
+
+fn serve(addr: String, options: Options) -> Result<(), String> {
+    if options.time_control != TimeControl::default() {
+        return Err("Served games are timed by --timeout and --on-timeout, \
+                    not --round-time, --bank or --on-flag"
+            .to_string());
+    }
+    if options.fog.is_some() && options.commit_reveal {
+        return Err("Fog of war cannot hide moves revealed in commit-reveal games".to_string());
+    }
+    let rules = Rules::default()
+        .with_moves_per_round(options.mov_num)
+        .with_fog(options.fog);
+    let mut server = Server::bind(&addr, rules)
+        .map_err(|e| format!("Could not listen on {}: {}", addr, e))?
+        .with_spectator_delay(options.spectator_delay);
+    if options.commit_reveal {
+        server = server.with_commit_reveal();
+    }
+    if let Some(timeout) = options.round_timeout {
+        server = server.with_round_timeout(timeout, options.on_timeout);
+    }
+    if let Some(grace) = options.reconnect_grace {
+        server = server.with_reconnect_grace(grace);
+    }
+    let local = server.local_addr().map_err(|e| e.to_string())?;
+    println!("Listening on {}", local);
+    server.run().map_err(|e| format!("Server stopped: {}", e))
+}
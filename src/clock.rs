@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+/// What happens to a player whose time runs out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagPolicy {
+    /// The moves handed in too late are dropped, the round is played without them.
+    EmptyMoves,
+    /// The player loses the game.
+    Loss,
+}
+
+/// Chess clock style time control: a deadline per round, a time bank with an
+/// increment per round, or both.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimeControl {
+    round_limit: Option<Duration>,
+    bank: Option<Duration>,
+    increment: Duration,
+    on_flag: FlagPolicy,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            round_limit: None,
+            bank: None,
+            increment: Duration::from_secs(0),
+            on_flag: FlagPolicy::EmptyMoves,
+        }
+    }
+}
+
+impl TimeControl {
+    /// Get TimeControl giving each player at most `limit` per round.
+    pub fn with_round_limit(mut self, limit: Duration) -> Self {
+        self.round_limit = Some(limit);
+        self
+    }
+
+    /// Get TimeControl giving each player `bank` for the whole game, with
+    /// `increment` added after every round.
+    pub fn with_bank(mut self, bank: Duration, increment: Duration) -> Self {
+        self.bank = Some(bank);
+        self.increment = increment;
+        self
+    }
+
+    /// Get TimeControl applying `policy` to players out of time.
+    pub fn with_flag_policy(mut self, policy: FlagPolicy) -> Self {
+        self.on_flag = policy;
+        self
+    }
+
+    /// Get the time control's on_flag.
+    pub fn on_flag(&self) -> FlagPolicy {
+        self.on_flag
+    }
+}
+
+/// Both players' clocks under a time control.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Option<Duration>; 2],
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: [control.bank, control.bank],
+        }
+    }
+
+    /// Get the clock's time control.
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// How long `player` may take for the current round, `None` if unlimited.
+    pub fn allowance(&self, player: usize) -> Option<Duration> {
+        match (self.control.round_limit, self.remaining[player]) {
+            (Some(limit), Some(left)) => Some(limit.min(left)),
+            (limit, left) => limit.or(left),
+        }
+    }
+
+    /// Charge `player` for a round that took `used`, returns whether the
+    /// player stayed within their allowance. The increment is added either way.
+    pub fn charge(&mut self, player: usize, used: Duration) -> bool {
+        let in_time = self.allowance(player).is_none_or(|allowed| used <= allowed);
+        if let Some(left) = &mut self.remaining[player] {
+            *left = left.saturating_sub(used) + self.control.increment;
+        }
+        in_time
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+    use crate::bot::Bot;
+    use crate::events::SessionEvent;
+    use crate::testing::players;
+    use crate::{Controller, Map, Moves, Player, Session, SessionResult};
+
+    /// Bot passing after thinking longer than the test's time limits.
+    struct SlowBot;
+
+    impl Bot for SlowBot {
+        fn choose_moves(&mut self, _map: &Map, _player: &Player, _mov_num: usize) -> Moves {
+            thread::sleep(Duration::from_millis(20));
+            [None, None, None]
+        }
+    }
+
+    fn timed_session(policy: FlagPolicy) -> Session {
+        let control = TimeControl::default()
+            .with_round_limit(Duration::from_millis(5))
+            .with_flag_policy(policy);
+        Session::new(players()).with_time_control(control)
+    }
+
+    #[test]
+    fn test_bank_and_increment() {
+        let second = Duration::from_secs(1);
+        let mut clock = Clock::new(TimeControl::default().with_bank(3 * second, second));
+        assert_eq!(clock.allowance(0), Some(3 * second));
+        assert!(clock.charge(0, 2 * second));
+        assert_eq!(clock.allowance(0), Some(2 * second));
+        assert_eq!(clock.allowance(1), Some(3 * second));
+        assert!(!clock.charge(0, 3 * second));
+        assert_eq!(clock.allowance(0), Some(second));
+    }
+
+    #[test]
+    fn test_round_limit() {
+        let second = Duration::from_secs(1);
+        let mut clock = Clock::new(TimeControl::default());
+        assert_eq!(clock.allowance(0), None);
+        assert!(clock.charge(0, 100 * second));
+
+        let control = TimeControl::default()
+            .with_round_limit(2 * second)
+            .with_bank(5 * second, Duration::from_secs(0));
+        let mut clock = Clock::new(control);
+        assert_eq!(clock.allowance(1), Some(2 * second));
+        assert!(!clock.charge(1, 3 * second));
+        assert_eq!(clock.allowance(1), Some(2 * second));
+        assert!(clock.charge(1, 2 * second));
+        assert_eq!(clock.allowance(1), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_session_flag_fall() {
+        let mut controllers = [
+            Controller::Bot(Box::new(SlowBot)),
+            Controller::Bot(Box::new(SlowBot)),
+        ];
+        let mut session = timed_session(FlagPolicy::EmptyMoves);
+        let expired = Arc::new(Mutex::new(vec![]));
+        let sink = expired.clone();
+        session.subscribe(Box::new(move |event: &SessionEvent| {
+            if let SessionEvent::TimeExpired { player } = event {
+                sink.lock().unwrap().push(*player);
+            }
+        }));
+        session.calculate_round(&mut controllers);
+        assert_eq!(*expired.lock().unwrap(), vec![0, 1]);
+        assert_eq!(session.record().len(), 2);
+        assert!(session.is_active());
+
+        let mut session = timed_session(FlagPolicy::Loss);
+        session.calculate_round(&mut controllers);
+        assert_eq!(session.record().len(), 1);
+        match session.result() {
+            SessionResult::Finished(winner) => assert_eq!(winner.name(), "Gura"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::record::format_side;
+use crate::Position;
+
+/// Hash of a move list and a nonce, published before the moves are revealed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Commitment([u8; 32]);
+
+impl Commitment {
+    /// Commit to `squares`, the nonce keeps the opponent from guessing them.
+    pub fn new(squares: &[(Position, Position)], nonce: &str) -> Self {
+        Self(sha256(
+            format!("{}:{}", nonce, format_side(squares)).as_bytes(),
+        ))
+    }
+
+    /// Whether `squares` and `nonce` are what was committed to.
+    pub fn matches(&self, squares: &[(Position, Position)], nonce: &str) -> bool {
+        *self == Commitment::new(squares, nonce)
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = CommitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(CommitError::Malformed);
+        }
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte =
+                u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| CommitError::Malformed)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CommitError {
+    /// The commitment is not 64 hex digits.
+    Malformed,
+    /// The player already committed this round.
+    AlreadyCommitted,
+    /// The player reveals without having committed.
+    NotCommitted,
+    /// Moves are revealed before the opponent committed.
+    OpponentNotCommitted,
+    /// The player already revealed this round.
+    AlreadyRevealed,
+    /// The revealed moves and nonce do not hash to the commitment.
+    Mismatch,
+}
+
+impl fmt::Display for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitError::Malformed => write!(f, "commitment must be 64 hex digits"),
+            CommitError::AlreadyCommitted => write!(f, "already committed this round"),
+            CommitError::NotCommitted => write!(f, "reveal without commitment"),
+            CommitError::OpponentNotCommitted => write!(f, "opponent has not committed yet"),
+            CommitError::AlreadyRevealed => write!(f, "already revealed this round"),
+            CommitError::Mismatch => write!(f, "reveal does not match commitment"),
+        }
+    }
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 digest of `data`.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for (k, w) in K.iter().zip(w.iter()) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(*w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+            *word = word.wrapping_add(*add);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::engine::{RejectReason, RoundEvent};
+    use crate::testing::players;
+    use crate::{Session, SessionResult};
+
+    #[test]
+    fn test_sha256_vectors() {
+        assert_eq!(
+            Commitment(sha256(b"")).to_string(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            Commitment(sha256(b"abc")).to_string(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let long = [b'a'; 1000];
+        assert_eq!(
+            Commitment(sha256(&long)).to_string(),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn test_commitment_round_trip() {
+        let squares = [(Position::new(0, 2), Position::new(0, 3))];
+        let commitment = Commitment::new(&squares, "salt");
+        assert_eq!(commitment.to_string().parse(), Ok(commitment));
+        assert!(commitment.matches(&squares, "salt"));
+        assert!(!commitment.matches(&squares, "pepper"));
+        assert!(!commitment.matches(&[], "salt"));
+        assert_eq!("beef".parse::<Commitment>(), Err(CommitError::Malformed));
+    }
+
+    #[test]
+    fn test_session_rejects_mismatched_reveal() {
+        let mut session = Session::new(players());
+        let ours = [(Position::new(0, 2), Position::new(0, 3))];
+        session.commit(0, Commitment::new(&ours, "a")).unwrap();
+        assert_eq!(
+            session.reveal(0, &ours, "a"),
+            Err(CommitError::OpponentNotCommitted)
+        );
+        assert_eq!(
+            session.commit(0, Commitment::new(&[], "a")),
+            Err(CommitError::AlreadyCommitted)
+        );
+        session.commit(1, Commitment::new(&[], "b")).unwrap();
+
+        let changed = [(Position::new(0, 2), Position::new(0, 4))];
+        assert_eq!(session.reveal(0, &changed, "a"), Err(CommitError::Mismatch));
+        assert_eq!(session.reveal(0, &ours, "a"), Ok(None));
+        assert_eq!(session.record().len(), 1);
+        assert!(session.reveal(1, &[], "b").unwrap().is_some());
+        assert_eq!(session.record().len(), 2);
+        assert!(session.get_last_map().is_taken(Position::new(0, 3)));
+        assert_eq!(
+            session.reveal(0, &ours, "a"),
+            Err(CommitError::NotCommitted)
+        );
+    }
+
+    #[test]
+    fn test_reveal_of_opponent_entities() {
+        let mut session = Session::new(players());
+        // A matching commitment does not make the second player's pawn ours.
+        let theirs = [(Position::new(15, 13), Position::new(15, 12))];
+        session.commit(0, Commitment::new(&theirs, "a")).unwrap();
+        session.commit(1, Commitment::new(&[], "b")).unwrap();
+        assert_eq!(session.reveal(0, &theirs, "a"), Ok(None));
+        let events = session.reveal(1, &[], "b").unwrap().unwrap();
+        assert!(matches!(
+            events[..],
+            [RoundEvent::MoveRejected {
+                player: 0,
+                reason: RejectReason::NotOwned,
+                ..
+            }]
+        ));
+        assert!(session.get_last_map().is_taken(Position::new(15, 13)));
+    }
+
+    #[test]
+    fn test_forfeit_moves_of_late_player() {
+        let mut session = Session::new(players());
+        let ours = [(Position::new(0, 2), Position::new(0, 3))];
+        session.commit(0, Commitment::new(&ours, "a")).unwrap();
+        assert!(session.forfeit_moves(1).is_none());
+        assert!(session.reveal(0, &ours, "a").unwrap().is_some());
+        assert!(session.get_last_map().is_taken(Position::new(0, 3)));
+
+        session.suspend();
+        assert!(matches!(session.result(), SessionResult::Suspended));
+        session.resume();
+        assert!(session.is_active());
+    }
+}
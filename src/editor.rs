@@ -0,0 +1,226 @@
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::render::Renderer;
+use crate::scenario::{parse_owner, parse_points, parse_square, Scenario, ScenarioError};
+use crate::{Entity, Map, Position, State};
+
+const HELP: &str = "Commands:
+  place OWNER FIGURE X Y [POINTS]  put an entity on a free square
+  remove X Y                       take the entity off the square
+  set X Y owner OWNER              hand the entity to the other player
+  set X Y figure FIGURE            swap the figure, points start full again
+  set X Y points N                 set points, 1 to 255
+  set X Y state alive|dead         set whether the entity is alive
+  at X Y                           describe the entity on the square
+  clear                            remove every entity
+  name, players, moves, rounds, objective, figure, terrain, position
+                                   set them as in a scenario file
+  show                             draw the board
+  check                            list what keeps the board from being played
+  position                         print the board as a position string
+  save FILE                        write a valid board as a scenario file
+  quit                             leave the editor";
+
+/// Line based editor building a scenario from an empty board.
+pub struct Editor {
+    scenario: Scenario,
+    /// Directory figure files are read from.
+    base: PathBuf,
+    renderer: Renderer,
+}
+
+impl Editor {
+    pub fn new(renderer: Renderer) -> Self {
+        Self {
+            scenario: Scenario::default(),
+            base: PathBuf::from("."),
+            renderer,
+        }
+    }
+
+    /// Read commands from `input` until `quit` or its end, replies and
+    /// errors are written to `out`.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", self.renderer.render(&self.scenario.map, &[]))?;
+        writeln!(out, "Type help for commands")?;
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            match line.trim() {
+                "quit" => break,
+                "" => continue,
+                command => match self.execute(i + 1, command) {
+                    Ok(reply) => writeln!(out, "{}", reply)?,
+                    Err(e) => writeln!(out, "Error: {}", e)?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry out one command, returns what to show.
+    fn execute(&mut self, line_no: usize, command: &str) -> Result<String, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words[..] {
+            ["help"] => return Ok(HELP.to_string()),
+            ["show"] => {}
+            ["check"] => {
+                let problems = self.scenario.problems();
+                return Ok(match problems.is_empty() {
+                    true => "The board is valid".to_string(),
+                    false => problems.join("\n"),
+                });
+            }
+            ["position"] => return Ok(self.scenario.position()),
+            ["save", path] => return self.save(Path::new(path)),
+            ["at", x, y] => {
+                let e = self.entity_at(x, y)?;
+                return Ok(format!(
+                    "{} of P{}, {} points, {}",
+                    e.figure.name(),
+                    e.owner_id,
+                    e.points,
+                    match e.state {
+                        State::Alive => "alive",
+                        State::Dead => "dead",
+                    }
+                ));
+            }
+            ["place", ref spec @ ..] => {
+                let entity = self.scenario.entity(spec)?;
+                self.scenario.place(entity)?;
+            }
+            ["remove", x, y] => {
+                let at = self.entity_at(x, y)?.position;
+                self.scenario.map.positions[at.x as usize][at.y as usize] = None;
+            }
+            ["set", x, y, field, value] => {
+                let mut e = self.entity_at(x, y)?;
+                match field {
+                    "owner" => e.owner_id = parse_owner(value)?,
+                    "figure" => {
+                        let figure = self.scenario.figure(value).ok_or("unknown figure")?;
+                        e = Entity::new(e.id, e.owner_id, figure, e.position);
+                        if !self.scenario.map.can_enter(&e, e.position) {
+                            return Err("the figure cannot stand on the square's terrain".into());
+                        }
+                    }
+                    "points" => e.points = parse_points(value)?,
+                    "state" => {
+                        e.state = match value {
+                            "alive" => State::Alive,
+                            "dead" => State::Dead,
+                            _ => return Err("the state is alive or dead".to_string()),
+                        }
+                    }
+                    _ => return Err(format!("cannot set {}", field)),
+                }
+                let at = e.position;
+                self.scenario.map.positions[at.x as usize][at.y as usize] = Some(e);
+            }
+            ["clear"] => self.scenario.map.positions = Map::empty().positions,
+            _ => match self.scenario.apply(line_no, command, &self.base) {
+                Ok(()) => {}
+                Err(ScenarioError::Syntax(_, message)) if message == "unknown key" => {
+                    return Err(format!("unknown command {}, type help", words[0]))
+                }
+                Err(ScenarioError::Syntax(_, message)) => return Err(message),
+                Err(e) => return Err(e.to_string()),
+            },
+        }
+        Ok(self.renderer.render(&self.scenario.map, &[]))
+    }
+
+    /// The entity on the square given by `x` and `y`.
+    fn entity_at(&self, x: &str, y: &str) -> Result<Entity, String> {
+        let at: Position = parse_square(x, y)?;
+        self.scenario.map.positions[at.x as usize][at.y as usize]
+            .clone()
+            .ok_or_else(|| format!("there is no entity at {}", at))
+    }
+
+    fn save(&self, path: &Path) -> Result<String, String> {
+        let problems = self.scenario.problems();
+        if !problems.is_empty() {
+            return Err(format!("not saved, {}", problems.join(", ")));
+        }
+        self.scenario
+            .save(path)
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        Ok(format!("Saved {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::terrain::Terrain;
+
+    fn edit(commands: &str) -> (Editor, String) {
+        let mut editor = Editor::new(Renderer::plain());
+        let mut out = vec![];
+        editor.run(commands.as_bytes(), &mut out).unwrap();
+        (editor, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_place_and_edit() {
+        let (editor, out) = edit(
+            "place 1 Queen 7 7\nplace 2 King 9 9 16\nplace 2 Pawn 9 9\n\
+             set 9 9 figure pawn\nset 7 7 points 20\nremove 0 0\nat 9 9\nquit\nplace 1 Pawn 0 0",
+        );
+        assert!(out.contains("Error: the square is taken"));
+        assert!(out.contains("Error: there is no entity at (0, 0)"));
+        assert!(out.contains("Pawn of P2, 16 points, alive"));
+        assert_eq!(editor.scenario.position(), "1 Queen 7 7 20, 2 Pawn 9 9 16");
+
+        // The position string reads back as the same board.
+        let mut copy = Scenario::default();
+        let line = format!("position {}", editor.scenario.position());
+        copy.apply(1, &line, Path::new(".")).unwrap();
+        assert_eq!(copy.map, editor.scenario.map);
+    }
+
+    #[test]
+    fn test_terrain_stays() {
+        let (editor, out) = edit(
+            "terrain water 3 3\nterrain wall 5 5\nplace 1 Queen 3 3\n\
+             set 3 3 figure pawn\nat 3 3\nclear",
+        );
+        assert!(out.contains("Error: the figure cannot stand on the square's terrain"));
+        assert!(out.contains("Queen of P1"));
+        // Clearing the board takes the entities off, the terrain stays.
+        assert_eq!(editor.scenario.map.entities().count(), 0);
+        assert_eq!(
+            editor.scenario.map.terrain(Position::new(3, 3)),
+            Terrain::Water
+        );
+        assert_eq!(
+            editor.scenario.map.terrain(Position::new(5, 5)),
+            Terrain::Wall
+        );
+    }
+
+    #[test]
+    fn test_check_and_save() {
+        let (editor, out) = edit(
+            "place 1 Queen 7 7\ncheck\nobjective 1 destroy King\nplace 2 King 9 9\n\
+             set 9 9 state dead\ncheck\nfly away",
+        );
+        assert!(out.contains("P2 has no entities"));
+        assert!(out.contains("King at (9, 9) is dead"));
+        assert!(out.contains("Error: unknown command fly, type help"));
+        let path = std::env::temp_dir().join("not_so_chess_editor_test.txt");
+        assert!(editor.save(&path).is_err());
+
+        let (editor, _) = edit("name Hunt\nplace 1 Queen 7 7\nplace 2 King 9 9\nrounds 2");
+        assert_eq!(editor.scenario.problems(), Vec::<String>::new());
+        editor.save(&path).unwrap();
+        let saved = Scenario::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(saved.name, "Hunt");
+        assert_eq!(saved.rounds, 2);
+        assert_eq!(saved.map, editor.scenario.map);
+    }
+}
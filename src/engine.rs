@@ -0,0 +1,323 @@
+use std::fmt;
+
+use crate::rules::Rules;
+use crate::{Entity, Map, Moves, Position, State};
+
+/// Why a submitted move was not played.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RejectReason {
+    /// There is no entity on the square the move starts from.
+    NoEntity,
+    /// The entity on the square belongs to another player.
+    NotOwned,
+    /// The target is off the board, taken, blocked or out of pattern.
+    Illegal,
+    /// The player submitted more moves than the rules allow.
+    TooManyMoves,
+}
+
+/// Something that happened while resolving a round, `player` is the index
+/// of the submitting player in the session.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RoundEvent {
+    Moved {
+        player: usize,
+        entity: Entity,
+        to: Position,
+    },
+    MoveRejected {
+        player: usize,
+        from: Position,
+        to: Position,
+        reason: RejectReason,
+    },
+    Collision {
+        position: Position,
+        first: Entity,
+        second: Entity,
+    },
+    DamageDealt {
+        attacker: Entity,
+        target: Entity,
+        amount: u8,
+    },
+    EntityDied {
+        entity: Entity,
+    },
+}
+
+impl fmt::Display for RoundEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundEvent::Moved { player, entity, to } => write!(
+                f,
+                "P{}: {} {} -> {}",
+                player + 1,
+                entity.figure.symbol(),
+                entity.position,
+                to
+            ),
+            RoundEvent::MoveRejected {
+                player,
+                from,
+                to,
+                reason,
+            } => write!(
+                f,
+                "P{}: illegal move {} -> {} ({:?}), losing this move",
+                player + 1,
+                from,
+                to,
+                reason
+            ),
+            RoundEvent::Collision {
+                position,
+                first,
+                second,
+            } => write!(
+                f,
+                "Bitka pany! {} and {} meet at {}",
+                first.figure.symbol(),
+                second.figure.symbol(),
+                position
+            ),
+            RoundEvent::DamageDealt {
+                attacker,
+                target,
+                amount,
+            } => write!(
+                f,
+                "{} {} deals {} damage to {} {}",
+                attacker.figure.symbol(),
+                attacker.position,
+                amount,
+                target.figure.symbol(),
+                target.position
+            ),
+            RoundEvent::EntityDied { entity } => {
+                write!(f, "{} {} died", entity.figure.symbol(), entity.position)
+            }
+        }
+    }
+}
+
+/// Resolve a round: both move lists are applied to `map` independently and
+/// the resulting maps are merged. `owners` are the ids of the players
+/// submitting `p1` and `p2`.
+pub fn resolve_round(
+    map: &Map,
+    p1: &Moves,
+    p2: &Moves,
+    owners: [u128; 2],
+    rules: &Rules,
+) -> (Map, Vec<RoundEvent>) {
+    let (m1, mut events) = apply_moves(map, p1, 0, owners[0], rules);
+    let (m2, p2_events) = apply_moves(map, p2, 1, owners[1], rules);
+    events.extend(p2_events);
+    let (resolved, merge_events) = merge(map, &m1, &m2);
+    events.extend(merge_events);
+    (resolved, events)
+}
+
+/// Apply one player's moves in order, skipping the ones that can not be
+/// played, entities not owned by `owner_id` among them.
+pub fn apply_moves(
+    map: &Map,
+    moves: &Moves,
+    player: usize,
+    owner_id: u128,
+    rules: &Rules,
+) -> (Map, Vec<RoundEvent>) {
+    let mut map = map.clone();
+    let mut events = vec![];
+    for (i, (e, t)) in moves.iter().flatten().enumerate() {
+        let reason = if i >= rules.moves_per_round() {
+            Some(RejectReason::TooManyMoves)
+        } else if !map.is_taken(e.position) {
+            Some(RejectReason::NoEntity)
+        } else if map.positions[e.position.x as usize][e.position.y as usize]
+            .as_ref()
+            .is_some_and(|on| on.owner_id != owner_id)
+        {
+            Some(RejectReason::NotOwned)
+        } else if !map.move_entity(e.position, *t) {
+            Some(RejectReason::Illegal)
+        } else {
+            None
+        };
+        events.push(match reason {
+            Some(reason) => RoundEvent::MoveRejected {
+                player,
+                from: e.position,
+                to: *t,
+                reason,
+            },
+            None => RoundEvent::Moved {
+                player,
+                entity: e.clone(),
+                to: *t,
+            },
+        });
+    }
+    (map, events)
+}
+
+/// Merge maps both players submitted against `m0`.
+pub fn merge(m0: &Map, m1: &Map, m2: &Map) -> (Map, Vec<RoundEvent>) {
+    let mut events = vec![];
+    let mut resolved_map = m0.clone();
+    for i in 0..16 {
+        for j in 0..16 {
+            let (e0, e1, e2) = (
+                m0.positions[i][j].clone(),
+                m1.positions[i][j].clone(),
+                m2.positions[i][j].clone(),
+            );
+
+            if e0.is_none() && e1.is_none() && e2.is_none() {
+                resolved_map.positions[i][j] = None;
+            } else if e0.is_some() && e1.is_none() && e2.is_some() {
+                resolved_map.positions[i][j] = e1;
+            } else if e0.is_some() && e1.is_some() && e2.is_none() {
+                resolved_map.positions[i][j] = None;
+            } else if e0.is_none() && e1.is_none() && e2.is_some() {
+                resolved_map.positions[i][j] = e2;
+            } else if e0.is_none() && e1.is_some() && e2.is_none() {
+                resolved_map.positions[i][j] = e1;
+            } else if e1.is_some() && e2.is_some() && e1 != e2 {
+                let mut e1 = e1.unwrap();
+                let mut e2 = e2.unwrap();
+                events.push(RoundEvent::Collision {
+                    position: Position::new(i as u8, j as u8),
+                    first: e1.clone(),
+                    second: e2.clone(),
+                });
+                let defence = m0.terrain(Position::new(i as u8, j as u8)).defence();
+                if e1.points > e2.points {
+                    events.extend(fight(&mut e1, &mut e2, defence));
+                    resolved_map.positions[i][j] = Some(e1);
+                } else if e1.points < e2.points {
+                    events.extend(fight(&mut e2, &mut e1, defence));
+                    resolved_map.positions[i][j] = Some(e2);
+                } else {
+                    // Equal points, neither entity makes it to the square.
+                    events.push(RoundEvent::EntityDied { entity: e1 });
+                    events.push(RoundEvent::EntityDied { entity: e2 });
+                }
+            }
+        }
+    }
+    (resolved_map, events)
+}
+
+/// The stronger entity survives, taking the weaker one's points as damage
+/// less the `defence` of the square.
+fn fight(winner: &mut Entity, loser: &mut Entity, defence: u8) -> Vec<RoundEvent> {
+    let before = winner.points;
+    loser.deal_dmg(winner);
+    winner.points = winner.points.saturating_add(defence).min(before);
+    loser.state = State::Dead;
+    vec![
+        RoundEvent::DamageDealt {
+            attacker: loser.clone(),
+            target: winner.clone(),
+            amount: before - winner.points,
+        },
+        RoundEvent::EntityDied {
+            entity: loser.clone(),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::{pawn, queen};
+    use crate::testing::place;
+
+    #[test]
+    fn test_moves_and_rejections() {
+        let mut map = Map::empty();
+        let e = place(&mut map, 1, pawn(), 7, 7);
+        let rules = Rules::default().with_moves_per_round(1);
+        let far = Position::new(12, 12);
+        let (next, events) = resolve_round(
+            &map,
+            &[Some((e.clone(), far)), None, None],
+            &[None, None, None],
+            [1, 2],
+            &rules,
+        );
+        assert_eq!(next, map);
+        assert_eq!(
+            events,
+            vec![RoundEvent::MoveRejected {
+                player: 0,
+                from: e.position,
+                to: far,
+                reason: RejectReason::Illegal
+            }]
+        );
+
+        let to = Position::new(7, 8);
+        let moves = [Some((e.clone(), to)), Some((e.clone(), far)), None];
+        let (next, events) = resolve_round(&map, &moves, &[None, None, None], [1, 2], &rules);
+        assert!(next.is_taken(to));
+        assert!(matches!(events[0], RoundEvent::Moved { player: 0, .. }));
+        assert!(matches!(
+            events[1],
+            RoundEvent::MoveRejected {
+                reason: RejectReason::TooManyMoves,
+                ..
+            }
+        ));
+
+        // The second player can not move the first player's entities.
+        let (next, events) = resolve_round(
+            &map,
+            &[None, None, None],
+            &[Some((e.clone(), to)), None, None],
+            [1, 2],
+            &rules,
+        );
+        assert_eq!(next, map);
+        assert_eq!(
+            events,
+            vec![RoundEvent::MoveRejected {
+                player: 1,
+                from: e.position,
+                to,
+                reason: RejectReason::NotOwned
+            }]
+        );
+    }
+
+    #[test]
+    fn test_collision_events() {
+        let mut map = Map::empty();
+        let weak = place(&mut map, 1, pawn(), 7, 5);
+        let strong = place(&mut map, 2, queen(), 7, 10);
+        let target = Position::new(7, 7);
+        let (next, events) = resolve_round(
+            &map,
+            &[Some((weak.clone(), target)), None, None],
+            &[Some((strong.clone(), target)), None, None],
+            [1, 2],
+            &Rules::default(),
+        );
+        let survivor = next.positions[7][7].clone().unwrap();
+        assert_eq!(survivor.owner_id, 2);
+        assert_eq!(survivor.points, 16);
+        assert_eq!(next.entities().count(), 1);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, RoundEvent::Collision { position, .. } if *position == target)));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, RoundEvent::DamageDealt { amount: 16, .. })));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, RoundEvent::EntityDied { entity } if entity.owner_id == 1)));
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::ParseFloatError;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{FigureType, Map, Player};
+
+/// Tunable weights of the static evaluation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Weights {
+    /// Per point of an entity.
+    pub material: f64,
+    /// Per entity of `FigureType::Special`.
+    pub special: f64,
+    /// Per point of an entity standing on an attacked square.
+    pub threatened: f64,
+    /// Per legal move.
+    pub mobility: f64,
+    /// Per attacker of the leader.
+    pub leader_attacked: f64,
+    /// Once when the leader is gone.
+    pub leader_lost: f64,
+    /// Name of the figure leading the set.
+    pub leader: String,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            material: 1.0,
+            special: 8.0,
+            threatened: 0.5,
+            mobility: 0.1,
+            leader_attacked: 10.0,
+            leader_lost: 1000.0,
+            leader: "King".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    IoError(io::Error),
+    ParseError(usize, ParseFloatError),
+    SyntaxError(usize),
+    UnknownKey(usize, String),
+}
+impl From<io::Error> for WeightsError {
+    fn from(error: io::Error) -> Self {
+        WeightsError::IoError(error)
+    }
+}
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsError::IoError(e) => write!(f, "{}", e),
+            WeightsError::ParseError(line, e) => write!(f, "line {}: {}", line, e),
+            WeightsError::SyntaxError(line) => write!(f, "line {}: expected `key = value`", line),
+            WeightsError::UnknownKey(line, key) => write!(f, "line {}: unknown key {}", line, key),
+        }
+    }
+}
+
+/// Parses `key = value` lines, `#` starts a comment, missing keys keep defaults.
+impl FromStr for Weights {
+    type Err = WeightsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Weights::default();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(WeightsError::SyntaxError(line_no)),
+            };
+            let number = || {
+                value
+                    .parse::<f64>()
+                    .map_err(|e| WeightsError::ParseError(line_no, e))
+            };
+            match key {
+                "material" => weights.material = number()?,
+                "special" => weights.special = number()?,
+                "threatened" => weights.threatened = number()?,
+                "mobility" => weights.mobility = number()?,
+                "leader_attacked" => weights.leader_attacked = number()?,
+                "leader_lost" => weights.leader_lost = number()?,
+                "leader" => weights.leader = value.to_string(),
+                _ => return Err(WeightsError::UnknownKey(line_no, key.to_string())),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+impl Weights {
+    /// Load weights from a config file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WeightsError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Score the map from the player's perspective, positive is good for them.
+    pub fn evaluate(&self, map: &Map, player: &Player) -> f64 {
+        self.side_score(map, player.id, true) - self.side_score(map, player.id, false)
+    }
+
+    /// Score of the player's side (`own`) or of everybody else.
+    fn side_score(&self, map: &Map, player_id: u128, own: bool) -> f64 {
+        let mine = |owner_id: u128| (owner_id == player_id) == own;
+        let enemy_attacks = map.attack_mask(|owner_id| !mine(owner_id));
+        let mut score = 0.0;
+        let mut has_leader = false;
+        for e in map.entities().filter(|e| mine(e.owner_id)) {
+            let attackers = enemy_attacks[e.position.x as usize][e.position.y as usize];
+            score += self.material * e.points as f64;
+            if *e.figure.fig_type() == FigureType::Special {
+                score += self.special;
+            }
+            if attackers > 0 {
+                score -= self.threatened * e.points as f64;
+            }
+            if e.figure.name() == self.leader {
+                has_leader = true;
+                score -= self.leader_attacked * attackers as f64;
+            }
+            score += self.mobility * map.legal_moves(e).len() as f64;
+        }
+        if !has_leader {
+            score -= self.leader_lost;
+        }
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::{king, pawn, queen};
+    use crate::testing::{place, player};
+
+    #[test]
+    fn test_opening_is_balanced() {
+        let map = Map::init(player(1), player(2));
+        let weights = Weights::default();
+        assert!(weights.evaluate(&map, &player(1)).abs() < 1e-9);
+        assert!(weights.evaluate(&map, &player(2)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_material_and_leader() {
+        let mut map = Map::empty();
+        place(&mut map, 1, king(), 0, 0);
+        place(&mut map, 1, queen(), 0, 8);
+        place(&mut map, 2, king(), 15, 15);
+        let weights = Weights::default();
+        assert!(weights.evaluate(&map, &player(1)) > 0.0);
+        map.positions[15][15] = None;
+        assert!(weights.evaluate(&map, &player(1)) > weights.leader_lost);
+    }
+
+    #[test]
+    fn test_threatened_pieces() {
+        let mut map = Map::empty();
+        place(&mut map, 1, king(), 0, 0);
+        place(&mut map, 2, king(), 15, 15);
+        place(&mut map, 1, queen(), 7, 7);
+        place(&mut map, 2, pawn(), 12, 12);
+        let weights = Weights {
+            mobility: 0.0,
+            ..Weights::default()
+        };
+        let calm = weights.evaluate(&map, &player(1));
+        // The pawn reaches the queen, the queen does not reach adjacent squares.
+        map.positions[12][12] = None;
+        place(&mut map, 2, pawn(), 7, 8);
+        let threatened = weights.evaluate(&map, &player(1));
+        assert!((calm - threatened - weights.threatened * 32.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_weights_from_str() {
+        let weights: Weights = "# tuned\nmaterial = 2\nmobility=0.25 # more\nleader = Queen\n"
+            .parse()
+            .unwrap();
+        assert_eq!(weights.material, 2.0);
+        assert_eq!(weights.mobility, 0.25);
+        assert_eq!(weights.leader, "Queen");
+        assert_eq!(weights.special, Weights::default().special);
+        assert!(matches!(
+            "speed = 3".parse::<Weights>(),
+            Err(WeightsError::UnknownKey(1, _))
+        ));
+        assert!(matches!(
+            "\nmaterial = x".parse::<Weights>(),
+            Err(WeightsError::ParseError(2, _))
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::engine::RoundEvent;
+use crate::{Map, Moves, SessionResult};
+
+/// Something that happened in a session, `player` is the index of the
+/// player in the session.
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// A player handed in moves for the current round.
+    MoveSubmitted { player: usize, moves: Moves },
+    /// A player ran out of time for the current round.
+    TimeExpired { player: usize },
+    /// Moves, rejections, collisions, damage and deaths while resolving.
+    Round(RoundEvent),
+    /// The round with the given number was added to the record.
+    RoundEnded { round: usize, map: Box<Map> },
+    /// The session is no longer active.
+    GameFinished { result: SessionResult },
+}
+
+impl fmt::Display for SessionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionEvent::MoveSubmitted { player, moves } => write!(
+                f,
+                "P{} submitted {} moves",
+                player + 1,
+                moves.iter().flatten().count()
+            ),
+            SessionEvent::TimeExpired { player } => write!(f, "P{} ran out of time", player + 1),
+            SessionEvent::Round(event) => write!(f, "{}", event),
+            SessionEvent::RoundEnded { round, .. } => write!(f, "Round {} ended", round),
+            SessionEvent::GameFinished { result } => write!(f, "Game finished: {:?}", result),
+        }
+    }
+}
+
+/// Receives every event of the session it is registered with.
+pub trait Observer: Send {
+    fn notify(&mut self, event: &SessionEvent);
+}
+
+impl<F: FnMut(&SessionEvent) + Send> Observer for F {
+    fn notify(&mut self, event: &SessionEvent) {
+        self(event)
+    }
+}
+
+/// Observer passing on what a spectator may see, `delay` rounds late:
+/// submissions are never shown and a round's events only once it is resolved.
+/// Everything left is passed on when the game finishes.
+pub struct Spectator<F> {
+    delay: usize,
+    current: Vec<SessionEvent>,
+    pending: VecDeque<Vec<SessionEvent>>,
+    sink: F,
+}
+
+impl<F: FnMut(&SessionEvent) + Send> Spectator<F> {
+    pub fn new(delay: usize, sink: F) -> Self {
+        Self {
+            delay,
+            current: vec![],
+            pending: VecDeque::new(),
+            sink,
+        }
+    }
+
+    fn release(&mut self, keep: usize) {
+        while self.pending.len() > keep {
+            for event in self.pending.pop_front().unwrap() {
+                (self.sink)(&event);
+            }
+        }
+    }
+}
+
+impl<F: FnMut(&SessionEvent) + Send> Observer for Spectator<F> {
+    fn notify(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::MoveSubmitted { .. } => {}
+            SessionEvent::TimeExpired { .. } | SessionEvent::Round(_) => {
+                self.current.push(event.clone())
+            }
+            SessionEvent::RoundEnded { .. } => {
+                self.current.push(event.clone());
+                self.pending.push_back(std::mem::take(&mut self.current));
+                self.release(self.delay);
+            }
+            SessionEvent::GameFinished { .. } => {
+                self.current.push(event.clone());
+                self.pending.push_back(std::mem::take(&mut self.current));
+                self.release(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::rules::Rules;
+    use crate::testing::players;
+    use crate::Session;
+
+    #[test]
+    fn test_observer_receives_round() {
+        let mut session = Session::new(players());
+        let seen = Arc::new(Mutex::new(vec![]));
+        let sink = seen.clone();
+        session.subscribe(Box::new(move |event: &SessionEvent| {
+            sink.lock().unwrap().push(event.clone())
+        }));
+
+        let map = session.get_last_map();
+        let e = map.entities().find(|e| e.owner_id == 1).unwrap().clone();
+        let far = crate::Position::new(e.position.x, 15 - e.position.y);
+        session.play_round([[Some((e, far)), None, None], [None, None, None]]);
+
+        let seen = seen.lock().unwrap();
+        assert!(matches!(
+            seen[0],
+            SessionEvent::MoveSubmitted { player: 0, .. }
+        ));
+        assert!(matches!(
+            seen[1],
+            SessionEvent::MoveSubmitted { player: 1, .. }
+        ));
+        assert!(matches!(
+            seen[2],
+            SessionEvent::Round(RoundEvent::MoveRejected { player: 0, .. })
+        ));
+        assert!(matches!(seen[3], SessionEvent::RoundEnded { round: 1, .. }));
+        assert_eq!(seen.len(), 4);
+    }
+
+    #[test]
+    fn test_game_finished() {
+        let mut session = Session::new(players());
+        for row in session.record[0].positions.iter_mut() {
+            for square in row.iter_mut() {
+                if square.as_ref().is_some_and(|e| e.owner_id == 2) {
+                    *square = None;
+                }
+            }
+        }
+        let finished = Arc::new(Mutex::new(false));
+        let sink = finished.clone();
+        session.subscribe(Box::new(move |event: &SessionEvent| {
+            if let SessionEvent::GameFinished { result } = event {
+                *sink.lock().unwrap() = matches!(result, SessionResult::Finished(_));
+            }
+        }));
+        session.play_round([[None, None, None], [None, None, None]]);
+        assert!(*finished.lock().unwrap());
+    }
+
+    #[test]
+    fn test_delayed_spectator() {
+        let mut session = Session::new(players()).with_rules(Rules::unlimited());
+        let seen = Arc::new(Mutex::new(vec![]));
+        let sink = seen.clone();
+        session.subscribe(Box::new(Spectator::new(2, move |event: &SessionEvent| {
+            sink.lock().unwrap().push(event.clone())
+        })));
+        let pass: [Moves; 2] = [[None, None, None], [None, None, None]];
+        session.play_round(pass.clone());
+        session.play_round(pass.clone());
+        assert!(seen.lock().unwrap().is_empty());
+        session.play_round(pass);
+        {
+            let seen = seen.lock().unwrap();
+            assert_eq!(seen.len(), 1);
+            assert!(matches!(seen[0], SessionEvent::RoundEnded { round: 1, .. }));
+        }
+        session.forfeit(0);
+        let seen = seen.lock().unwrap();
+        assert!(seen
+            .iter()
+            .all(|e| !matches!(e, SessionEvent::MoveSubmitted { .. })));
+        assert!(matches!(seen[2], SessionEvent::RoundEnded { round: 3, .. }));
+        assert!(matches!(seen[3], SessionEvent::GameFinished { .. }));
+    }
+}
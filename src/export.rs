@@ -0,0 +1,241 @@
+use std::fmt::Write;
+
+use crate::record::GameRecord;
+use crate::terrain::Terrain;
+use crate::{Map, Player, Position};
+
+/// Side of a square in SVG units.
+const SQUARE: usize = 40;
+/// Height of the stats below the board.
+const STATS: usize = 50;
+
+const COLOURS: [&str; 2] = ["#2b6cb0", "#c53030"];
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Top left corner of a square, the first row at the bottom.
+fn corner(position: Position) -> (usize, usize) {
+    (
+        position.x() as usize * SQUARE,
+        (15 - position.y() as usize) * SQUARE,
+    )
+}
+
+/// Index of the owner's colour and name.
+fn side(owner_id: u128) -> usize {
+    if owner_id == 1 {
+        0
+    } else {
+        1
+    }
+}
+
+fn centre(position: Position) -> (usize, usize) {
+    let (x, y) = corner(position);
+    (x + SQUARE / 2, y + SQUARE / 2)
+}
+
+/// Standalone SVG of `map`: entities in their owner's colour with symbol and
+/// points, `moves` as arrows, attacked entities outlined and each player's
+/// entities and points below the board.
+pub fn board_svg(map: &Map, names: &[String; 2], moves: &[(Position, Position)]) -> String {
+    let size = 16 * SQUARE;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace">"#,
+        w = size,
+        h = size + STATS
+    );
+    svg.push_str(
+        r##"<defs><marker id="arrow" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="6" markerHeight="6" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="#333"/></marker></defs>"##,
+    );
+    svg.push('\n');
+    for x in 0..16 {
+        for y in 0..16 {
+            let (left, top) = corner(Position::new(x, y));
+            let fill = match map.terrain(Position::new(x, y)) {
+                Terrain::Wall => "#4a4a4a",
+                Terrain::Water => "#90cdf4",
+                Terrain::Fort => "#d6bc8a",
+                Terrain::Plain if (x + y) % 2 == 0 => "#e8e4d8",
+                Terrain::Plain => "#f7f5ee",
+            };
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}"/>"#,
+                left,
+                top,
+                fill,
+                s = SQUARE
+            );
+        }
+    }
+
+    let attacked: Vec<Position> = map.entities().flat_map(|e| map.attacks(e)).collect();
+    for e in map.entities() {
+        let (left, top) = corner(e.position);
+        if attacked.contains(&e.position) {
+            let _ = writeln!(
+                svg,
+                r##"<rect class="attacked" x="{}" y="{}" width="{s}" height="{s}" fill="none" stroke="#dd6b20" stroke-width="3"/>"##,
+                left + 1,
+                top + 1,
+                s = SQUARE - 2
+            );
+        }
+        let (cx, cy) = centre(e.position);
+        let colour = COLOURS[side(e.owner_id)];
+        let _ = writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}"><title>{} of {}, {} points</title></circle>"#,
+            cx,
+            cy,
+            SQUARE * 2 / 5,
+            colour,
+            escape(e.figure.name()),
+            escape(&names[side(e.owner_id)]),
+            e.points
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" fill="white" font-size="16">{}</text>"#,
+            cx,
+            cy + 2,
+            escape(&e.figure.symbol().to_string())
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" fill="white" font-size="9">{}</text>"#,
+            cx,
+            cy + 12,
+            e.points
+        );
+    }
+
+    for (from, to) in moves {
+        let (x1, y1) = centre(*from);
+        let (x2, y2) = centre(*to);
+        let _ = writeln!(
+            svg,
+            r##"<line class="move" x1="{}" y1="{}" x2="{}" y2="{}" stroke="#333" stroke-width="2" marker-end="url(#arrow)"/>"##,
+            x1, y1, x2, y2
+        );
+    }
+
+    for (i, name) in names.iter().enumerate() {
+        let owned: Vec<u8> = map
+            .entities()
+            .filter(|e| side(e.owner_id) == i)
+            .map(|e| e.points)
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<text x="8" y="{}" fill="{}" font-size="14">P{} {}: {} entities, {} points</text>"#,
+            size + 20 + i * 20,
+            COLOURS[i],
+            i + 1,
+            escape(name),
+            owned.len(),
+            owned.iter().map(|p| *p as usize).sum::<usize>()
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// HTML page replaying a recorded game, a slider picks the round shown.
+pub fn game_html(record: &GameRecord, players: [Player; 2]) -> String {
+    let names = record.names().clone();
+    let session = record.replay(players, |_| {});
+    let maps = session.record();
+    let last = maps.len() - 1;
+    let mut html = String::new();
+    let _ = writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{} vs {}</title>\n</head>\n<body>",
+        escape(&names[0]),
+        escape(&names[1])
+    );
+    let _ = writeln!(
+        html,
+        "<h1>{} vs {}</h1>\n<p>Result: {}</p>",
+        escape(&names[0]),
+        escape(&names[1]),
+        escape(record.result())
+    );
+    let _ = writeln!(
+        html,
+        r#"<input type="range" id="slider" min="0" max="{last}" value="{last}" oninput="show(this.value)"> <span id="label"></span>"#,
+        last = last
+    );
+    for (round, map) in maps.iter().enumerate() {
+        let moves: Vec<(Position, Position)> = match round {
+            0 => vec![],
+            n => record.rounds()[n - 1].concat(),
+        };
+        let _ = writeln!(
+            html,
+            "<div class=\"round\" id=\"round-{}\">\n{}</div>",
+            round,
+            board_svg(map, &names, &moves)
+        );
+    }
+    let _ = writeln!(
+        html,
+        "<script>\nfunction show(n) {{\n  document.querySelectorAll('.round').forEach(function (d) {{ d.style.display = 'none'; }});\n  document.getElementById('round-' + n).style.display = 'block';\n  document.getElementById('label').textContent = 'Round ' + n;\n}}\nshow({});\n</script>\n</body>\n</html>",
+        last
+    );
+    html
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::standard_set;
+
+    fn players() -> [Player; 2] {
+        [
+            Player::new("Ame".to_string(), 1, standard_set()),
+            Player::new("<Gura>".to_string(), 2, standard_set()),
+        ]
+    }
+
+    #[test]
+    fn test_board_svg() {
+        let [p1, p2] = players();
+        let map = Map::init(p1, p2);
+        let names = ["Ame".to_string(), "<Gura>".to_string()];
+        let moves = [(Position::new(0, 2), Position::new(0, 3))];
+        let svg = board_svg(&map, &names, &moves);
+        assert!(svg.starts_with("<svg xmlns"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<circle").count(), map.entities().count());
+        assert_eq!(svg.matches("class=\"move\"").count(), 1);
+        assert!(svg.contains("P2 &lt;Gura&gt;: 8 entities, 160 points"));
+    }
+
+    #[test]
+    fn test_game_html() {
+        let mut record = GameRecord::new(["Ame".to_string(), "<Gura>".to_string()]);
+        let [p1, p2] = players();
+        let map = Map::init(p1, p2);
+        let pawn = map.positions[0][2].clone().unwrap();
+        record.push(&[
+            [Some((pawn, Position::new(0, 3))), None, None],
+            [None, None, None],
+        ]);
+        let html = game_html(&record, players());
+        assert!(html.contains(r#"max="1" value="1""#));
+        assert!(html.contains("id=\"round-0\""));
+        assert!(html.contains("id=\"round-1\""));
+        assert_eq!(html.matches("class=\"move\"").count(), 1);
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{Figure, FigureType, Position};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SymbolError {
+    /// Symbols are printable ASCII so boards can be drawn without glyphs.
+    NotPrintable(String, char),
+    /// Two figures share a symbol, case is not enough to tell them apart
+    /// as plain boards use it for the owner.
+    Clash(String, String, char),
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolError::NotPrintable(name, symbol) => {
+                write!(f, "{} has the unprintable symbol {:?}", name, symbol)
+            }
+            SymbolError::Clash(first, second, symbol) => {
+                write!(f, "{} and {} share the symbol {}", first, second, symbol)
+            }
+        }
+    }
+}
+
+/// Check that the figures of a session can be told apart by their symbols.
+/// Copies of a figure starting on other squares share its symbol.
+pub fn check_symbols<'a, I: IntoIterator<Item = &'a Figure>>(
+    figures: I,
+) -> Result<(), SymbolError> {
+    let mut seen: HashMap<char, &Figure> = HashMap::new();
+    for figure in figures {
+        let symbol = figure.symbol();
+        if !symbol.is_ascii_graphic() {
+            return Err(SymbolError::NotPrintable(figure.name().to_string(), symbol));
+        }
+        match seen.insert(symbol.to_ascii_lowercase(), figure) {
+            Some(other) if !same_figure(other, figure) => {
+                return Err(SymbolError::Clash(
+                    other.name().to_string(),
+                    figure.name().to_string(),
+                    symbol,
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether two figures only differ by where they start.
+fn same_figure(a: &Figure, b: &Figure) -> bool {
+    let moved = Figure {
+        start_position: b.start_position,
+        ..a.clone()
+    };
+    moved == *b
+}
+
+pub fn pawn() -> Figure {
+    Figure::new(
+        "Pawn".to_string(),
+        'p',
+        [
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 1, 0, 0, 0, 0],
+            [0, 0, 0, 0, 1, 0, 0, 0, 0],
+            [0, 0, 1, 1, 0, 1, 1, 0, 0],
+            [0, 0, 0, 0, 1, 0, 0, 0, 0],
+            [0, 0, 0, 0, 1, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ],
+        Position { x: 0, y: 0 },
+        FigureType::Normal,
+    )
+    .with_glyphs('♙', '♟')
+}
+
+pub fn king() -> Figure {
+    Figure::new(
+        "King".to_string(),
+        'K',
+        [
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 1, 1, 1, 0, 0, 0],
+            [0, 0, 0, 1, 0, 1, 0, 0, 0],
+            [0, 0, 0, 1, 1, 1, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ],
+        Position { x: 1, y: 2 },
+        FigureType::Special,
+    )
+    .with_glyphs('♔', '♚')
+}
+
+pub fn queen() -> Figure {
+    Figure::new(
+        "Queen".to_string(),
+        'Q',
+        [
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 1, 1, 1, 0, 1, 1, 1, 1],
+            [1, 1, 1, 0, 0, 0, 1, 1, 1],
+            [1, 1, 1, 1, 0, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+        ],
+        Position { x: 2, y: 1 },
+        FigureType::Special,
+    )
+    .with_glyphs('♕', '♛')
+    .with_water_crossing()
+}
+
+// Not part of any set yet
+pub fn bishop() -> Figure {
+    Figure::new(
+        "Bishop".to_string(),
+        'B',
+        [
+            [1, 0, 0, 0, 0, 0, 0, 0, 1],
+            [0, 1, 0, 0, 0, 0, 0, 1, 0],
+            [0, 0, 1, 0, 0, 0, 1, 0, 0],
+            [0, 0, 0, 1, 0, 1, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 1, 0, 1, 0, 0, 0],
+            [0, 0, 1, 0, 0, 0, 1, 0, 0],
+            [0, 1, 0, 0, 0, 0, 0, 1, 0],
+            [1, 0, 0, 0, 0, 0, 0, 0, 1],
+        ],
+        Position { x: 3, y: 3 },
+        FigureType::Special,
+    )
+    .with_glyphs('♗', '♝')
+}
+
+/// The default set both players start with.
+pub fn standard_set() -> [Figure; 8] {
+    let pawn = pawn();
+    [
+        queen(),
+        king(),
+        pawn.clone().starting_at(Position { x: 2, y: 2 }),
+        pawn.clone().starting_at(Position { x: 1, y: 0 }),
+        pawn.clone().starting_at(Position { x: 0, y: 1 }),
+        pawn.clone().starting_at(Position { x: 0, y: 2 }),
+        pawn.clone().starting_at(Position { x: 2, y: 0 }),
+        pawn,
+    ]
+}
+
+/// Built-in figure by name, ignoring case.
+pub fn by_name(name: &str) -> Option<Figure> {
+    match name.to_lowercase().as_str() {
+        "pawn" => Some(pawn()),
+        "king" => Some(king()),
+        "queen" => Some(queen()),
+        "bishop" => Some(bishop()),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum FigureError {
+    Io(io::Error),
+    /// The line with the given number could not be read.
+    Syntax(usize, String),
+    Missing(&'static str),
+}
+impl From<io::Error> for FigureError {
+    fn from(error: io::Error) -> Self {
+        FigureError::Io(error)
+    }
+}
+impl fmt::Display for FigureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FigureError::Io(e) => write!(f, "{}", e),
+            FigureError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            FigureError::Missing(field) => write!(f, "no {} given", field),
+        }
+    }
+}
+
+/// Read a figure file, `#` starts a comment:
+///
+/// ```text
+/// name Berserker
+/// symbol b
+/// type normal
+/// start 0 0
+/// glyphs ♘ ♞
+/// crosses water
+/// pattern
+/// . . . . x . . . .
+/// ...
+/// ```
+///
+/// The nine pattern rows are drawn as on the board, the figure's square
+/// `@` in the middle, `x` marks squares it may move to.
+pub fn parse_figure(s: &str) -> Result<Figure, FigureError> {
+    let mut name = None;
+    let mut symbol = None;
+    let mut fig_type = FigureType::Normal;
+    let mut start = Position::new(0, 0);
+    let mut glyphs = None;
+    let mut crosses_water = false;
+    let mut rows: Vec<[u8; 9]> = vec![];
+    let mut in_pattern = false;
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let syntax = |message: &str| FigureError::Syntax(line_no, message.to_string());
+        if in_pattern {
+            let mut row = [0; 9];
+            let cells: Vec<&str> = line.split_whitespace().collect();
+            if cells.len() != 9 || rows.len() == 9 {
+                return Err(syntax("pattern rows have 9 cells, there are 9 rows"));
+            }
+            for (cell, token) in row.iter_mut().zip(cells) {
+                *cell = match token {
+                    "x" | "1" => 1,
+                    "." | "0" | "@" => 0,
+                    _ => return Err(syntax("pattern cells are x, . or @")),
+                };
+            }
+            rows.push(row);
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = value.trim();
+        match key {
+            "name" => name = Some(value.to_string()),
+            "symbol" => match value.chars().collect::<Vec<_>>()[..] {
+                [c] => symbol = Some(c),
+                _ => return Err(syntax("the symbol is one character")),
+            },
+            "type" => {
+                fig_type = match value {
+                    "normal" => FigureType::Normal,
+                    "special" => FigureType::Special,
+                    _ => return Err(syntax("type is normal or special")),
+                }
+            }
+            "start" => start = value.parse().map_err(|_| syntax("start is x y"))?,
+            "glyphs" => match value
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<Vec<_>>()[..]
+            {
+                [white, black] => glyphs = Some((white, black)),
+                _ => return Err(syntax("glyphs are two characters")),
+            },
+            "crosses" if value == "water" => crosses_water = true,
+            "pattern" => in_pattern = true,
+            _ => return Err(syntax("unknown key")),
+        }
+    }
+    if rows.len() != 9 {
+        return Err(FigureError::Missing("pattern"));
+    }
+    // Rows run from dy 4 down to -4, columns from dx -4 to 4, the pattern
+    // itself is indexed [4 - dx][4 - dy].
+    let mut pattern = [[0; 9]; 9];
+    for (r, row) in rows.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            pattern[8 - c][r] = *cell;
+        }
+    }
+    let name = name.ok_or(FigureError::Missing("name"))?;
+    let symbol = symbol.ok_or(FigureError::Missing("symbol"))?;
+    let mut figure = Figure::new(name, symbol, pattern, start, fig_type);
+    if let Some((white, black)) = glyphs {
+        figure = figure.with_glyphs(white, black);
+    }
+    if crosses_water {
+        figure = figure.with_water_crossing();
+    }
+    Ok(figure)
+}
+
+pub fn load_figure<P: AsRef<Path>>(path: P) -> Result<Figure, FigureError> {
+    parse_figure(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_check_symbols() {
+        assert_eq!(
+            check_symbols(standard_set().iter().chain([bishop()].iter())),
+            Ok(())
+        );
+        let berserker = Figure::new(
+            "Berserker".to_string(),
+            'b',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        assert_eq!(
+            check_symbols([bishop(), berserker].iter()),
+            Err(SymbolError::Clash(
+                "Bishop".to_string(),
+                "Berserker".to_string(),
+                'b'
+            ))
+        );
+        // A figure named like the pawn but moving otherwise is another one.
+        let impostor = Figure::new(
+            "Pawn".to_string(),
+            'p',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        assert_eq!(
+            check_symbols(standard_set().iter().chain([impostor].iter())),
+            Err(SymbolError::Clash(
+                "Pawn".to_string(),
+                "Pawn".to_string(),
+                'p'
+            ))
+        );
+        let nameless = Figure::new(
+            String::new(),
+            '♟',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        assert!(matches!(
+            check_symbols([nameless].iter()),
+            Err(SymbolError::NotPrintable(..))
+        ));
+    }
+}
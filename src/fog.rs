@@ -0,0 +1,206 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::engine::RoundEvent;
+use crate::terrain::Terrain;
+use crate::{Map, Position};
+
+/// Squares a player sees, indexed `[x][y]`.
+pub type Visibility = [[bool; 16]; 16];
+
+/// How far entities see when fog of war is on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Vision {
+    /// Squares at most this many steps away in any direction that no wall
+    /// hides.
+    Radius(u8),
+    /// Squares the entity could move to or attack, lines blocked as for moves.
+    Pattern,
+}
+
+impl FromStr for Vision {
+    type Err = String;
+
+    /// Parse `pattern` or `radius:N`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "pattern" => Ok(Vision::Pattern),
+            Some(("radius", radius)) => radius
+                .parse()
+                .map(Vision::Radius)
+                .map_err(|e| format!("Invalid radius {}: {}", radius, e)),
+            _ => Err(format!(
+                "Invalid vision {}, expected pattern or radius:N",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Vision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Vision::Radius(radius) => write!(f, "radius:{}", radius),
+            Vision::Pattern => write!(f, "pattern"),
+        }
+    }
+}
+
+impl Map {
+    /// Squares the entities of `owner_id` see, their own squares included.
+    pub fn visibility(&self, owner_id: u128, vision: Vision) -> Visibility {
+        let mut visible = [[false; 16]; 16];
+        for e in self.entities().filter(|e| e.owner_id == owner_id) {
+            for (x, column) in visible.iter_mut().enumerate() {
+                for (y, seen) in column.iter_mut().enumerate() {
+                    let target = Position::new(x as u8, y as u8);
+                    *seen |= target == e.position
+                        || match vision {
+                            Vision::Radius(radius) => {
+                                let dx = (x as i16 - e.position.x as i16).abs();
+                                let dy = (y as i16 - e.position.y as i16).abs();
+                                dx.max(dy) <= radius as i16 && self.in_sight(e.position, target)
+                            }
+                            Vision::Pattern => {
+                                e.can_reach(target) && !self.is_blocked(e.position, target)
+                            }
+                        };
+                }
+            }
+        }
+        visible
+    }
+
+    /// Whether no wall stands on the squares of the line between `from` and
+    /// `to`, rounded to the nearest square at every step.
+    pub fn in_sight(&self, from: Position, to: Position) -> bool {
+        let dx = to.x as i16 - from.x as i16;
+        let dy = to.y as i16 - from.y as i16;
+        let steps = dx.abs().max(dy.abs());
+        let along = |start: u8, d: i16, step: i16| {
+            (start as i16 + (2 * d * step + steps * d.signum()) / (2 * steps)) as u8
+        };
+        (1..steps).all(|step| {
+            let between = Position::new(along(from.x, dx, step), along(from.y, dy, step));
+            self.terrain(between) != Terrain::Wall
+        })
+    }
+
+    /// Copy of the map as `owner_id` sees it: entities on unseen squares are
+    /// left out, terrain is known everywhere.
+    pub fn view(&self, owner_id: u128, vision: Vision) -> Map {
+        let visible = self.visibility(owner_id, vision);
+        let mut view = self.clone();
+        for (x, column) in visible.iter().enumerate() {
+            for (y, seen) in column.iter().enumerate() {
+                if !seen {
+                    view.positions[x][y] = None;
+                }
+            }
+        }
+        view
+    }
+}
+
+/// Whether the player with index `player` learns of the event, seeing the
+/// `visible` squares. Players always learn about their own moves.
+pub fn is_seen(event: &RoundEvent, player: usize, visible: &Visibility) -> bool {
+    let sees = |p: &Position| visible[p.x as usize][p.y as usize];
+    match event {
+        RoundEvent::Moved {
+            player: mover,
+            entity,
+            to,
+        } => *mover == player || sees(&entity.position) || sees(to),
+        RoundEvent::MoveRejected { player: mover, .. } => *mover == player,
+        RoundEvent::Collision { position, .. } => sees(position),
+        RoundEvent::DamageDealt { target, .. } => sees(&target.position),
+        RoundEvent::EntityDied { entity } => sees(&entity.position),
+    }
+}
+
+/// Squares seen on either map, what a player saw of a round from its start
+/// to its end.
+pub fn either(before: &Visibility, after: &Visibility) -> Visibility {
+    let mut visible = *before;
+    for (column, other) in visible.iter_mut().zip(after.iter()) {
+        for (seen, other) in column.iter_mut().zip(other.iter()) {
+            *seen |= other;
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::{pawn, queen};
+    use crate::testing::place;
+
+    #[test]
+    fn test_parse_vision() {
+        assert_eq!("pattern".parse(), Ok(Vision::Pattern));
+        assert_eq!("radius:3".parse(), Ok(Vision::Radius(3)));
+        assert!("radius".parse::<Vision>().is_err());
+        assert_eq!(Vision::Radius(2).to_string(), "radius:2");
+    }
+
+    #[test]
+    fn test_views() {
+        let mut map = Map::empty();
+        place(&mut map, 1, pawn(), 0, 0);
+        place(&mut map, 2, pawn(), 3, 3);
+        place(&mut map, 2, queen(), 0, 2);
+        map.set_terrain(Position::new(9, 9), Terrain::Water);
+
+        let near = map.view(1, Vision::Radius(2));
+        assert!(near.positions[0][2].is_some());
+        assert!(near.positions[3][3].is_none());
+        assert_eq!(near.terrain(Position::new(9, 9)), Terrain::Water);
+        // The pawn sees two squares up, the queen there blocks the view.
+        let pattern = map.visibility(1, Vision::Pattern);
+        assert!(pattern[0][1] && pattern[0][2] && pattern[2][0]);
+        assert!(!pattern[1][1] && !pattern[3][3]);
+        // Its own view is never fogged.
+        assert_eq!(map.view(2, Vision::Radius(0)).entities().count(), 2);
+    }
+
+    #[test]
+    fn test_walls_hide_within_radius() {
+        let mut map = Map::empty();
+        place(&mut map, 1, pawn(), 0, 0);
+        place(&mut map, 2, pawn(), 3, 0);
+        place(&mut map, 2, pawn(), 3, 3);
+        map.set_terrain(Position::new(2, 0), Terrain::Wall);
+        map.set_terrain(Position::new(1, 2), Terrain::Wall);
+
+        let visible = map.visibility(1, Vision::Radius(3));
+        assert!(visible[2][0] && visible[3][3] && visible[3][1]);
+        assert!(!visible[3][0] && !visible[2][3]);
+        assert!(map.view(1, Vision::Radius(3)).positions[3][0].is_none());
+    }
+
+    #[test]
+    fn test_events_seen() {
+        let mut map = Map::empty();
+        let far = place(&mut map, 2, pawn(), 12, 12);
+        place(&mut map, 1, pawn(), 0, 0);
+        let visible = map.visibility(1, Vision::Radius(3));
+        let moved = RoundEvent::Moved {
+            player: 1,
+            entity: far.clone(),
+            to: Position::new(12, 14),
+        };
+        assert!(!is_seen(&moved, 0, &visible));
+        assert!(is_seen(&moved, 1, &visible));
+        let close = RoundEvent::Moved {
+            player: 1,
+            entity: far,
+            to: Position::new(2, 2),
+        };
+        assert!(is_seen(&close, 0, &visible));
+        let after = map.visibility(1, Vision::Radius(0));
+        assert_eq!(either(&after, &visible), visible);
+    }
+}
@@ -0,0 +1,125 @@
+//! Standard input read by a thread of its own, so waiting for a human player
+//! can stop once their time runs out.
+
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+/// Bytes of standard input, the thread reading them starts on first use.
+fn stdin_bytes() -> &'static Mutex<Receiver<u8>> {
+    static BYTES: OnceLock<Mutex<Receiver<u8>>> = OnceLock::new();
+    BYTES.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes().map_while(Result::ok) {
+                if sender.send(byte).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(receiver)
+    })
+}
+
+/// Input read until a deadline, reads fail with `TimedOut` once it passed.
+pub struct Timed<'a> {
+    bytes: &'a Mutex<Receiver<u8>>,
+    deadline: Option<Instant>,
+}
+
+impl Timed<'static> {
+    /// Standard input until `deadline`, `None` waits as long as it takes.
+    pub fn stdin(deadline: Option<Instant>) -> Self {
+        Self {
+            bytes: stdin_bytes(),
+            deadline,
+        }
+    }
+}
+
+impl<'a> Timed<'a> {
+    /// Read a line into `buf` like `BufRead::read_line`, newline included.
+    pub fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        let mut line = vec![];
+        let mut byte = [0];
+        while self.read(&mut byte)? == 1 {
+            line.push(byte[0]);
+            if byte[0] == b'\n' {
+                break;
+            }
+        }
+        buf.push_str(&String::from_utf8_lossy(&line));
+        Ok(line.len())
+    }
+}
+
+impl<'a> Read for Timed<'a> {
+    /// Wait for the first byte, then take the ones already there.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let bytes = self.bytes.lock().unwrap();
+        let first = match self.deadline {
+            Some(deadline) => {
+                match bytes.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(byte) => byte,
+                    Err(RecvTimeoutError::Timeout) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "out of time"))
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                }
+            }
+            None => match bytes.recv() {
+                Ok(byte) => byte,
+                Err(_) => return Ok(0),
+            },
+        };
+        buf[0] = first;
+        let mut read = 1;
+        while read < buf.len() {
+            match bytes.try_recv() {
+                Ok(byte) => buf[read] = byte,
+                Err(_) => break,
+            }
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_read_until_deadline() {
+        let (sender, receiver) = mpsc::channel();
+        let bytes = Mutex::new(receiver);
+        let mut input = Timed {
+            bytes: &bytes,
+            deadline: Some(Instant::now() + Duration::from_millis(50)),
+        };
+        for byte in b"0 2\n0 3" {
+            sender.send(*byte).unwrap();
+        }
+        let mut line = String::new();
+        assert_eq!(input.read_line(&mut line).unwrap(), 4);
+        assert_eq!(line, "0 2\n");
+        // The rest of a line typed too late is not waited for.
+        let error = input.read_line(&mut line).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        drop(sender);
+        let mut input = Timed {
+            bytes: &bytes,
+            deadline: None,
+        };
+        assert_eq!(input.read(&mut [0; 4]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{Entity, Figure, Map, Position};
+
+/// Pattern cell `dx` squares right of and `dy` squares above the figure.
+fn cell(figure: &Figure, dx: i8, dy: i8) -> u8 {
+    figure.pattern()[(4 - dx) as usize][(4 - dy) as usize]
+}
+
+/// The pattern as seen on the board, `@` the figure, `x` squares it may reach.
+pub fn pattern_rows(figure: &Figure) -> Vec<String> {
+    (-4i8..5)
+        .rev()
+        .map(|dy| {
+            (-4i8..5)
+                .map(|dx| match cell(figure, dx, dy) {
+                    _ if dx == 0 && dy == 0 => "@",
+                    0 => ".",
+                    _ => "x",
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Everything odd about the figure's pattern.
+pub fn warnings(figure: &Figure) -> Vec<String> {
+    let mut warnings = vec![];
+    if cell(figure, 0, 0) != 0 {
+        warnings.push("the centre cell is set, it is the figure's own square".to_string());
+    }
+    let mut set = vec![];
+    for dx in -4i8..5 {
+        for dy in -4i8..5 {
+            match cell(figure, dx, dy) {
+                0 => {}
+                1 => set.push((dx, dy)),
+                v => warnings.push(format!("cell {} {} is {}, only 1 counts", dx, dy, v)),
+            }
+        }
+    }
+    if set.is_empty() {
+        warnings.push("the pattern is empty, the figure never moves".to_string());
+    }
+    let start = figure.start_position();
+    if !start.is_valid() {
+        warnings.push(format!("the start {} is off the board", start));
+        return warnings;
+    }
+    for (whose, from) in [("first", start), ("second", start.reverse())].iter() {
+        let off = set
+            .iter()
+            .filter(|(dx, dy)| {
+                let x = from.x() as i8 + dx;
+                let y = from.y() as i8 + dy;
+                !(0..16).contains(&x) || !(0..16).contains(&y)
+            })
+            .count();
+        if off > 0 {
+            warnings.push(format!(
+                "{} of {} cells are off the board from the {} player's start {}",
+                off,
+                set.len(),
+                whose,
+                from
+            ));
+        }
+    }
+    warnings
+}
+
+/// Describe the figure: its pattern, the squares it threatens from `at` on
+/// `board` as the first player's and warnings about the pattern.
+pub fn inspect(figure: &Figure, at: Position, board: &Map) -> String {
+    let mut lines = vec![format!(
+        "{} ({}), {}, {} points, starts at {}",
+        figure.name(),
+        figure.symbol(),
+        figure.fig_type(),
+        figure.full_points(),
+        figure.start_position()
+    )];
+    lines.push("Pattern:".to_string());
+    lines.extend(
+        pattern_rows(figure)
+            .into_iter()
+            .map(|row| format!("  {}", row)),
+    );
+
+    let mut map = board.clone();
+    let entity = Entity::new(0, 1, figure.clone(), at);
+    map.positions[at.x() as usize][at.y() as usize] = Some(entity.clone());
+    let moves = map.legal_moves(&entity);
+    let attacks = map.attacks(&entity);
+    lines.push(format!(
+        "From {}: {} moves, {} attacks",
+        at,
+        moves.len(),
+        attacks.len()
+    ));
+    for y in (0..16).rev() {
+        let mut row = format!("{:X} ", y);
+        for x in 0..16 {
+            let square = Position::new(x, y);
+            let symbol = if square == at {
+                "@".to_string()
+            } else if attacks.contains(&square) {
+                "!".to_string()
+            } else if moves.contains(&square) {
+                "x".to_string()
+            } else {
+                match &map.positions[x as usize][y as usize] {
+                    Some(e) => e.get_symbol(),
+                    None => "_".to_string(),
+                }
+            };
+            row.push_str(&symbol);
+            row.push(' ');
+        }
+        lines.push(row.trim_end().to_string());
+    }
+    lines.push("  0 1 2 3 4 5 6 7 8 9 A B C D E F".to_string());
+
+    let warnings = warnings(figure);
+    if warnings.is_empty() {
+        lines.push("No warnings".to_string());
+    } else {
+        lines.push("Warnings:".to_string());
+        lines.extend(warnings.into_iter().map(|w| format!("- {}", w)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::{bishop, parse_figure, pawn};
+    use crate::FigureType;
+
+    #[test]
+    fn test_pattern_rows_round_trip() {
+        let pawn = pawn();
+        let rows = pattern_rows(&pawn);
+        assert_eq!(rows[2], ". . . . x . . . .");
+        assert_eq!(rows[4], ". . x x @ x x . .");
+        let file = format!(
+            "name Pawn\nsymbol p\nstart 0 0\nglyphs ♙ ♟\npattern\n{}\n",
+            rows.join("\n")
+        );
+        assert_eq!(parse_figure(&file).unwrap(), pawn);
+    }
+
+    #[test]
+    fn test_warnings() {
+        // The pawn starting in the corner cannot use half of its pattern.
+        let pawn_warnings = warnings(&pawn());
+        assert_eq!(pawn_warnings.len(), 2);
+        assert!(pawn_warnings[0].starts_with("4 of 8 cells are off the board from the first"));
+        assert_eq!(warnings(&bishop()).len(), 2);
+
+        let mut pattern = [[0; 9]; 9];
+        pattern[4][4] = 1;
+        let odd = crate::Figure::new(
+            "Odd".to_string(),
+            'o',
+            pattern,
+            Position::new(7, 7),
+            FigureType::Normal,
+        );
+        assert_eq!(
+            warnings(&odd),
+            vec!["the centre cell is set, it is the figure's own square".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_inspect_threats() {
+        let mut board = Map::empty();
+        let target = Entity::new(0, 2, pawn(), Position::new(9, 9));
+        board.positions[9][9] = Some(target);
+        let report = inspect(&bishop(), Position::new(7, 7), &board);
+        assert!(report.contains("From (7, 7): 13 moves, 1 attacks"));
+        assert!(report.contains("\n9 _ _ _ _ _ x _ _ _ ! _ _ _ _ _ _\n"));
+        assert!(report.contains("Warnings:\n- 3 of 16 cells"));
+    }
+}
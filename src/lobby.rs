@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::figures::{standard_set, SymbolError};
+use crate::rules::Rules;
+use crate::{Player, Session};
+
+/// How long a game nobody sits at is kept around.
+const IDLE_LIMIT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LobbyError {
+    NoSuchGame(u128),
+    GameFull(u128),
+    NameTaken(String),
+    NotInGame(String),
+    Symbols(SymbolError),
+}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LobbyError::NoSuchGame(id) => write!(f, "no game {}", id),
+            LobbyError::GameFull(id) => write!(f, "game {} is full", id),
+            LobbyError::NameTaken(name) => write!(f, "{} is already in this game", name),
+            LobbyError::NotInGame(name) => write!(f, "{} is not in this game", name),
+            LobbyError::Symbols(e) => write!(f, "invalid figure set: {}", e),
+        }
+    }
+}
+
+/// What the lobby shows about a game.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GameInfo {
+    pub id: u128,
+    pub name: String,
+    pub players: Vec<String>,
+    pub spectators: usize,
+    pub started: bool,
+}
+
+impl fmt::Display for GameInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}/2 {} {}",
+            self.id,
+            self.name,
+            self.players.len(),
+            self.spectators,
+            if self.started { "playing" } else { "waiting" }
+        )
+    }
+}
+
+struct Room {
+    name: String,
+    seats: [Option<String>; 2],
+    spectators: Vec<String>,
+    session: Option<Session>,
+    touched: Instant,
+}
+
+impl Room {
+    /// Whether a player or spectator of the room goes by `name`.
+    fn has(&self, name: &str) -> bool {
+        self.seats
+            .iter()
+            .flatten()
+            .chain(&self.spectators)
+            .any(|n| n == name)
+    }
+}
+
+/// Keeps track of every game, its players and spectators. A game's session
+/// starts once both seats are taken.
+pub struct Lobby {
+    rooms: BTreeMap<u128, Room>,
+    next_id: u128,
+    rules: Rules,
+    idle_limit: Duration,
+}
+
+impl Lobby {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            rooms: BTreeMap::new(),
+            next_id: 1,
+            rules,
+            idle_limit: IDLE_LIMIT,
+        }
+    }
+
+    /// Open a new game, returns its id.
+    pub fn create(&mut self, name: &str) -> u128 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.rooms.insert(
+            id,
+            Room {
+                name: name.to_string(),
+                seats: [None, None],
+                spectators: vec![],
+                session: None,
+                touched: Instant::now(),
+            },
+        );
+        id
+    }
+
+    /// Every game, oldest first.
+    pub fn list(&self) -> Vec<GameInfo> {
+        self.rooms
+            .iter()
+            .map(|(id, room)| GameInfo {
+                id: *id,
+                name: room.name.clone(),
+                players: room.seats.iter().flatten().cloned().collect(),
+                spectators: room.spectators.len(),
+                started: room.session.is_some(),
+            })
+            .collect()
+    }
+
+    /// The oldest game with a free seat, a new one if there is none.
+    pub fn open_game(&mut self) -> u128 {
+        let open = self
+            .rooms
+            .iter()
+            .find(|(_, room)| room.session.is_none() && room.seats.iter().any(Option::is_none));
+        match open {
+            Some((id, _)) => *id,
+            None => self.create("open"),
+        }
+    }
+
+    /// Take a seat, returns its index. The session starts with the second player.
+    pub fn join(&mut self, id: u128, player: &str) -> Result<usize, LobbyError> {
+        let rules = self.rules;
+        let room = self.rooms.get_mut(&id).ok_or(LobbyError::NoSuchGame(id))?;
+        if room.has(player) {
+            return Err(LobbyError::NameTaken(player.to_string()));
+        }
+        let seat = match room.seats.iter().position(Option::is_none) {
+            Some(seat) if room.session.is_none() => seat,
+            _ => return Err(LobbyError::GameFull(id)),
+        };
+        let mut seats = room.seats.clone();
+        seats[seat] = Some(player.to_string());
+        if let [Some(n1), Some(n2)] = &seats {
+            let session = Session::new([
+                Player::new(n1.clone(), 1, standard_set()),
+                Player::new(n2.clone(), 2, standard_set()),
+            ])
+            .with_id(id)
+            .with_rules(rules);
+            session.check_symbols().map_err(LobbyError::Symbols)?;
+            room.session = Some(session);
+        }
+        room.seats = seats;
+        room.touched = Instant::now();
+        Ok(seat)
+    }
+
+    /// Watch a game without taking part.
+    pub fn spectate(&mut self, id: u128, name: &str) -> Result<(), LobbyError> {
+        let room = self.rooms.get_mut(&id).ok_or(LobbyError::NoSuchGame(id))?;
+        if room.has(name) {
+            return Err(LobbyError::NameTaken(name.to_string()));
+        }
+        room.spectators.push(name.to_string());
+        Ok(())
+    }
+
+    /// Leave a game. A player leaving a started game forfeits it.
+    pub fn leave(&mut self, id: u128, name: &str) -> Result<(), LobbyError> {
+        let room = self.rooms.get_mut(&id).ok_or(LobbyError::NoSuchGame(id))?;
+        room.touched = Instant::now();
+        if let Some(i) = room.spectators.iter().position(|s| s == name) {
+            room.spectators.remove(i);
+            return Ok(());
+        }
+        let seat = room
+            .seats
+            .iter()
+            .position(|s| s.as_deref() == Some(name))
+            .ok_or_else(|| LobbyError::NotInGame(name.to_string()))?;
+        match &mut room.session {
+            Some(session) if !session.is_over() => session.forfeit(seat),
+            Some(_) => {}
+            None => room.seats[seat] = None,
+        }
+        Ok(())
+    }
+
+    /// Get the session of a started game.
+    pub fn session(&self, id: u128) -> Option<&Session> {
+        self.rooms.get(&id).and_then(|room| room.session.as_ref())
+    }
+
+    /// Get the session of a started game to play it.
+    pub fn session_mut(&mut self, id: u128) -> Option<&mut Session> {
+        let room = self.rooms.get_mut(&id)?;
+        room.touched = Instant::now();
+        room.session.as_mut()
+    }
+
+    /// Remove finished games and games nobody sat at for a while, returns
+    /// the ids removed.
+    pub fn cleanup(&mut self, now: Instant) -> Vec<u128> {
+        let idle_limit = self.idle_limit;
+        let stale: Vec<u128> = self
+            .rooms
+            .iter()
+            .filter(|(_, room)| match &room.session {
+                Some(session) => session.is_over(),
+                None => {
+                    room.seats.iter().all(Option::is_none)
+                        && now.saturating_duration_since(room.touched) >= idle_limit
+                }
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in stale.iter() {
+            self.rooms.remove(id);
+        }
+        stale
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::SessionResult;
+
+    #[test]
+    fn test_create_join_and_list() {
+        let mut lobby = Lobby::new(Rules::default());
+        let first = lobby.create("first");
+        let second = lobby.create("second");
+        assert_ne!(first, second);
+        assert_eq!(lobby.open_game(), first);
+
+        assert_eq!(lobby.join(first, "Ame"), Ok(0));
+        assert_eq!(
+            lobby.join(first, "Ame"),
+            Err(LobbyError::NameTaken("Ame".to_string()))
+        );
+        assert!(lobby.session(first).is_none());
+        assert_eq!(lobby.join(first, "Gura"), Ok(1));
+        assert_eq!(lobby.join(first, "Ina"), Err(LobbyError::GameFull(first)));
+        lobby.spectate(first, "Ina").unwrap();
+        for name in ["Ina", "Gura"] {
+            assert_eq!(
+                lobby.spectate(first, name),
+                Err(LobbyError::NameTaken(name.to_string()))
+            );
+        }
+        assert_eq!(lobby.open_game(), second);
+
+        let session = lobby.session(first).unwrap();
+        assert_eq!(session.id(), first);
+        assert_eq!(session.players()[1].name(), "Gura");
+        let info = &lobby.list()[0];
+        assert_eq!(info.players, vec!["Ame".to_string(), "Gura".to_string()]);
+        assert_eq!(info.spectators, 1);
+        assert!(info.started);
+        assert_eq!(lobby.join(7, "Ina"), Err(LobbyError::NoSuchGame(7)));
+    }
+
+    #[test]
+    fn test_leave_and_cleanup() {
+        let mut lobby = Lobby::new(Rules::default());
+        let waiting = lobby.create("waiting");
+        let playing = lobby.create("playing");
+        lobby.join(waiting, "Ame").unwrap();
+        lobby.leave(waiting, "Ame").unwrap();
+        assert_eq!(lobby.join(waiting, "Gura"), Ok(0));
+        lobby.leave(waiting, "Gura").unwrap();
+
+        lobby.join(playing, "Ame").unwrap();
+        lobby.join(playing, "Gura").unwrap();
+        assert!(lobby.cleanup(Instant::now()).is_empty());
+        lobby.leave(playing, "Ame").unwrap();
+        match lobby.session(playing).unwrap().result() {
+            SessionResult::Finished(winner) => assert_eq!(winner.name(), "Gura"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let later = Instant::now() + IDLE_LIMIT;
+        assert_eq!(lobby.cleanup(later), vec![waiting, playing]);
+        assert!(lobby.list().is_empty());
+    }
+
+    #[test]
+    fn test_both_players_gone() {
+        let mut lobby = Lobby::new(Rules::default());
+        let id = lobby.create("abandoned");
+        lobby.join(id, "Ame").unwrap();
+        lobby.join(id, "Gura").unwrap();
+        lobby.spectate(id, "Ina").unwrap();
+        lobby.leave(id, "Gura").unwrap();
+        lobby.leave(id, "Ame").unwrap();
+        match lobby.session(id).unwrap().result() {
+            SessionResult::Finished(winner) => assert_eq!(winner.name(), "Ame"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        // The game goes right away, spectators do not keep it around.
+        assert_eq!(lobby.cleanup(Instant::now()), vec![id]);
+        assert!(lobby.list().is_empty());
+    }
+}
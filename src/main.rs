@@ -1,95 +1,59 @@
+use std::env;
 use std::fmt::{self};
-use std::io::stdin;
 use std::num::ParseIntError;
 use std::ops::Sub;
 use std::str::FromStr;
+use std::time::Instant;
+
+mod bot;
+mod cli;
+mod clock;
+mod commit;
+mod editor;
+mod engine;
+mod eval;
+mod events;
+mod export;
+mod figures;
+mod fog;
+mod input;
+mod inspect;
+mod lobby;
+mod mcts;
+mod movegen;
+mod record;
+mod render;
+mod rng;
+mod rules;
+mod scenario;
+mod search;
+mod server;
+mod solver;
+mod terrain;
+#[cfg(test)]
+mod testing;
+mod tournament;
+mod tui;
+
+use bot::{Bot, BotOptions, RandomBot};
+use clock::{Clock, FlagPolicy, TimeControl};
+use commit::{CommitError, Commitment};
+use engine::RoundEvent;
+use events::{Observer, SessionEvent};
+use figures::SymbolError;
+use input::Timed;
+use mcts::MctsBot;
+use record::to_moves;
+use render::Renderer;
+use rng::Rng;
+use rules::{DrawReason, Rules};
+use search::MatrixBot;
+use terrain::Terrain;
+use tui::Tui;
 
 fn main() {
     // println!("Hello, world!");
-    let pawn = Figure::new(
-        "Pawn".to_string(),
-        [
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 1, 0, 0, 0, 0],
-            [0, 0, 0, 0, 1, 0, 0, 0, 0],
-            [0, 0, 1, 1, 0, 1, 1, 0, 0],
-            [0, 0, 0, 0, 1, 0, 0, 0, 0],
-            [0, 0, 0, 0, 1, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-        ],
-        Position { x: 0, y: 0 },
-        crate::FigureType::Normal,
-    );
-
-    let king = Figure::new(
-        "King".to_string(),
-        [
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 1, 1, 1, 0, 0, 0],
-            [0, 0, 0, 1, 0, 1, 0, 0, 0],
-            [0, 0, 0, 1, 1, 1, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-        ],
-        Position { x: 1, y: 2 },
-        crate::FigureType::Special,
-    );
-
-    let queen = Figure::new(
-        "Queen".to_string(),
-        [
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-            [1, 1, 1, 1, 0, 1, 1, 1, 1],
-            [1, 1, 1, 0, 0, 0, 1, 1, 1],
-            [1, 1, 1, 1, 0, 1, 1, 1, 1],
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-            [1, 1, 1, 1, 1, 1, 1, 1, 1],
-        ],
-        Position { x: 2, y: 1 },
-        crate::FigureType::Special,
-    );
-    let bishop = Figure::new(
-        "Bishop".to_string(),
-        [
-            [1, 0, 0, 0, 0, 0, 0, 0, 1],
-            [0, 1, 0, 0, 0, 0, 0, 1, 0],
-            [0, 0, 1, 0, 0, 0, 1, 0, 0],
-            [0, 0, 0, 1, 0, 1, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 1, 0, 1, 0, 0, 0],
-            [0, 0, 1, 0, 0, 0, 1, 0, 0],
-            [0, 1, 0, 0, 0, 0, 0, 1, 0],
-            [1, 0, 0, 0, 0, 0, 0, 0, 1],
-        ],
-        Position { x: 3, y: 3 },
-        crate::FigureType::Special,
-    );
-
-    let set = [
-        queen,
-        king,
-        pawn.clone().starting_at(Position { x: 2, y: 2 }),
-        pawn.clone().starting_at(Position { x: 1, y: 0 }),
-        pawn.clone().starting_at(Position { x: 0, y: 1 }),
-        pawn.clone().starting_at(Position { x: 0, y: 2 }),
-        pawn.clone().starting_at(Position { x: 2, y: 0 }),
-        pawn,
-    ];
-
-    let p1 = Player::new("Ame".to_string(), 1, set.clone());
-    let p2 = Player::new("Gura".to_string(), 2, set);
-    let mut session = Session::new([p1, p2]);
-
-    session.show();
-    session.calculate_round(1);
+    cli::run(env::args().skip(1));
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -100,33 +64,53 @@ pub enum FigureType {
 
 impl fmt::Display for FigureType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{:?}", self)
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Figure {
     name: String,
+    symbol: char,
+    glyphs: Option<[char; 2]>,
     pattern: [[u8; 9]; 9],
     start_position: Position,
     fig_type: FigureType,
+    crosses_water: bool,
 }
 
 impl Figure {
     pub fn new(
         name: String,
+        symbol: char,
         pattern: [[u8; 9]; 9],
         start_position: Position,
         fig_type: FigureType,
     ) -> Self {
         Self {
             name,
+            symbol,
+            glyphs: None,
             pattern,
             start_position,
             fig_type,
+            crosses_water: false,
         }
     }
 
+    /// Get Figure able to enter water.
+    pub fn with_water_crossing(mut self) -> Self {
+        self.crosses_water = true;
+        self
+    }
+
+    /// Get Figure drawn as `white` for the first player and `black` for the
+    /// second where Unicode is available.
+    pub fn with_glyphs(mut self, white: char, black: char) -> Self {
+        self.glyphs = Some([white, black]);
+        self
+    }
+
     /// Get Figure with updated position
     pub fn starting_at(&mut self, position: Position) -> Self {
         self.start_position = position;
@@ -143,6 +127,21 @@ impl Figure {
         self.name.as_ref()
     }
 
+    /// Get the figure's symbol, used on plain boards and in move notation.
+    pub fn symbol(&self) -> char {
+        self.symbol
+    }
+
+    /// Get the figure's glyphs for the first and second player.
+    pub fn glyphs(&self) -> Option<[char; 2]> {
+        self.glyphs
+    }
+
+    /// Get whether entities of the figure may enter water.
+    pub fn crosses_water(&self) -> bool {
+        self.crosses_water
+    }
+
     /// Get a reference to the figure's start_position.
     pub fn start_position(&self) -> Position {
         self.start_position
@@ -152,6 +151,15 @@ impl Figure {
     pub fn pattern(&self) -> [[u8; 9]; 9] {
         self.pattern
     }
+
+    /// Points of an undamaged entity of this figure.
+    pub fn full_points(&self) -> u8 {
+        let base: u8 = 16;
+        match self.fig_type {
+            FigureType::Normal => base,
+            FigureType::Special => 2 * base,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -275,18 +283,13 @@ impl fmt::Display for Entity {
 
 impl Entity {
     fn new(id: u8, owner_id: u128, figure: Figure, position: Position) -> Self {
-        let base: u8 = 16;
-        let points = match figure.fig_type() {
-            FigureType::Normal => 1,
-            FigureType::Special => 2,
-        };
         Self {
             id,
             owner_id,
             state: State::Alive,
+            points: figure.full_points(),
             figure,
             position,
-            points: points * base,
         }
     }
 
@@ -299,11 +302,7 @@ impl Entity {
     }
 
     pub fn get_symbol(&self) -> String {
-        let s = self.figure.name().chars().next().unwrap().to_string();
-        match self.figure.fig_type() {
-            FigureType::Normal => s.to_lowercase(),
-            FigureType::Special => s.to_uppercase(),
-        }
+        self.figure.symbol().to_string()
     }
 
     pub fn can_reach(&self, target: Position) -> bool {
@@ -324,10 +323,11 @@ impl Entity {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Map {
     // Consider changing it from 2d array to 1d
     positions: [[Option<Entity>; 16]; 16],
+    terrain: [[Terrain; 16]; 16],
 }
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -364,11 +364,25 @@ impl Map {
         const _INIT1: [Option<Entity>; 16] = [_INIT0; 16];
         Self {
             positions: [_INIT1; 16],
+            terrain: [[Terrain::Plain; 16]; 16],
         }
     }
 
+    /// Iterate over all entities on the map.
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.positions.iter().flatten().flatten()
+    }
+
+    /// Sum of points of the player's entities on the map.
+    pub fn points_of(&self, owner_id: u128) -> u32 {
+        self.entities()
+            .filter(|e| e.owner_id == owner_id)
+            .map(|e| e.points as u32)
+            .sum()
+    }
+
     fn is_taken(&self, position: Position) -> bool {
-        self.positions[position.x() as usize][position.y() as usize] != None
+        self.positions[position.x() as usize][position.y() as usize].is_some()
     }
 
     fn transpose(&mut self) {
@@ -390,6 +404,7 @@ impl Map {
         self.reverse_rows();
     }
     // TODO please rewrite this monster
+    #[allow(dead_code)]
     fn update_points(&mut self) {
         for i in 0..16 {
             for j in 0..16 {
@@ -471,18 +486,24 @@ impl Map {
             ));
         }
     }
-    // todo return result of OP
-    pub fn move_entity(&mut self, current: Position, target: Position) {
-        let mut entity = self.positions[current.x as usize][current.y as usize]
-            .clone()
-            .unwrap();
-        if self.positions[target.x as usize][target.y as usize] == None
-            && entity.can_reach(target)
-            && target.is_valid()
-        {
+    /// Get a copy of the map with moves of `owner_id` applied in order.
+    pub fn apply_moves(&self, moves: &Moves, owner_id: u128) -> Map {
+        engine::apply_moves(self, moves, 0, owner_id, &Rules::unlimited()).0
+    }
+
+    /// Move entity standing on current to target, returns whether it moved.
+    pub fn move_entity(&mut self, current: Position, target: Position) -> bool {
+        let mut entity = match &self.positions[current.x as usize][current.y as usize] {
+            Some(e) => e.clone(),
+            None => return false,
+        };
+        if self.is_legal(&entity, target) {
             self.positions[entity.position.x as usize][entity.position.y as usize] = None;
             entity.position = target;
             self.positions[target.x as usize][target.y as usize] = Some(entity);
+            true
+        } else {
+            false
         }
     }
 }
@@ -498,6 +519,7 @@ impl Player {
         Self { name, id, set }
     }
 
+    #[allow(dead_code)]
     fn fig(&self, index: usize) -> &Figure {
         &self.set[index]
     }
@@ -508,6 +530,7 @@ impl Player {
     }
 
     /// Set the player's set.
+    #[allow(dead_code)]
     fn set_set(&mut self, set: [Figure; 8]) {
         self.set = set;
     }
@@ -517,148 +540,429 @@ impl Player {
         self.name.as_ref()
     }
 }
+/// Who chooses moves for a player.
+pub enum Controller {
+    Human,
+    /// A human entering moves on the full-screen board.
+    Tui(Tui),
+    Bot(Box<dyn Bot>),
+}
+
+impl Controller {
+    /// Create a controller from its command line description `KIND[:SEED]`.
+    pub fn new(kind: &str, options: &BotOptions) -> Result<Self, String> {
+        let mut parts = kind.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let seed = match parts.next() {
+            Some(seed) => seed
+                .parse::<u64>()
+                .map_err(|e| format!("invalid seed {}: {}", seed, e))?,
+            None => Rng::from_time().next_u64(),
+        };
+        match name {
+            "human" => Ok(Controller::Human),
+            "random" => Ok(Controller::Bot(Box::new(RandomBot::new(seed)))),
+            "matrix" => {
+                let mut bot = MatrixBot::new(seed).with_weights(options.weights.clone());
+                if let Some(think) = options.think {
+                    bot = bot.with_time_limit(think);
+                }
+                if let Some(iterations) = options.iterations {
+                    bot = bot.with_iterations(iterations);
+                }
+                Ok(Controller::Bot(Box::new(bot)))
+            }
+            "mcts" => {
+                let mut bot = MctsBot::new(seed).with_weights(options.weights.clone());
+                if let Some(think) = options.think {
+                    bot = bot.with_time_limit(think);
+                }
+                if let Some(playouts) = options.playouts {
+                    bot = bot.with_playouts(playouts);
+                }
+                if let Some(threads) = options.threads {
+                    bot = bot.with_threads(threads);
+                }
+                Ok(Controller::Bot(Box::new(bot)))
+            }
+            _ => Err(format!("unknown player kind {}", kind)),
+        }
+    }
+}
+
+/// Moves submitted by a player in a single round.
+pub type Moves = [Option<(Entity, Position)>; 3];
+
 #[derive(Debug, Clone)]
 pub enum SessionResult {
     Finished(Box<Player>),
+    Draw(DrawReason),
     Active,
     Suspended,
 }
-#[derive(Debug, Clone)]
+impl fmt::Display for SessionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionResult::Finished(winner) => write!(f, "winner {}", winner.name()),
+            SessionResult::Draw(reason) => write!(f, "draw {:?}", reason),
+            SessionResult::Active => write!(f, "active"),
+            SessionResult::Suspended => write!(f, "suspended"),
+        }
+    }
+}
 pub struct Session {
     id: u128,
     players: [Player; 2],
     record: Vec<Map>,
     result: SessionResult,
+    rules: Rules,
+    observers: Vec<Option<Box<dyn Observer>>>,
+    commitments: [Option<Commitment>; 2],
+    revealed: [Option<Moves>; 2],
+    clock: Clock,
 }
 
 impl Session {
     pub fn new(players: [Player; 2]) -> Self {
         Self {
-            id: 1, // thats a temp dont worry
+            id: 0,
             players: players.clone(),
             record: vec![Map::init(players[0].clone(), players[1].clone())],
             result: SessionResult::Active,
+            rules: Rules::default(),
+            observers: vec![],
+            commitments: [None, None],
+            revealed: [None, None],
+            clock: Clock::new(TimeControl::default()),
         }
     }
-    // Not working properly rn, logic is wrong
-    fn calculate_round(&mut self, mov_num: usize) {
-        let p1 = self.players[0].clone();
-        let p2 = self.players[1].clone();
-        let m1 = self.record.last().unwrap().clone();
-        let m2 = self.record.last().unwrap().clone();
-        let mut maps = [m1, m2];
-        for (i, player) in [p1, p2].iter().enumerate() {
-            // let mut moves: Vec<Option<(Entity, Position)>> = vec![];
 
-            let mut moves: [Option<(Entity, Position)>; 3] = [None, None, None];
+    /// Get Session with the id a lobby issued for it.
+    pub fn with_id(mut self, id: u128) -> Self {
+        self.id = id;
+        self
+    }
 
-            let mut position_buffer = String::new();
-            let mut target_buffer = String::new();
+    /// Get the session's id, 0 unless a lobby manages it.
+    pub fn id(&self) -> u128 {
+        self.id
+    }
 
-            for i in 0..mov_num {
-                println!("P: {}; Move #{}", player.name(), &i + 1);
+    /// Get Session judged by given rules.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
 
-                println!("Position: ");
-                stdin().read_line(&mut position_buffer).unwrap();
+    /// Get Session starting from `map` instead of the opening position.
+    pub fn with_start(mut self, map: Map) -> Self {
+        self.record = vec![map];
+        self
+    }
 
-                println!("Move: {}", &position_buffer);
-                println!("Target: ");
+    /// Get Session whose players' moves are timed by `control`.
+    pub fn with_time_control(mut self, control: TimeControl) -> Self {
+        self.clock = Clock::new(control);
+        self
+    }
 
-                stdin().read_line(&mut target_buffer).unwrap();
-                println!("To: {}", &target_buffer);
+    /// Register an observer notified of every event from now on, returns
+    /// the index to unsubscribe it with.
+    pub fn subscribe(&mut self, observer: Box<dyn Observer>) -> usize {
+        self.observers.push(Some(observer));
+        self.observers.len() - 1
+    }
 
-                position_buffer.pop();
-                let position = Position::from_str(&position_buffer);
-                let target = Position::from_str(&target_buffer);
+    /// Stop notifying the observer registered under `index`.
+    pub fn unsubscribe(&mut self, index: usize) {
+        if let Some(observer) = self.observers.get_mut(index) {
+            *observer = None;
+        }
+    }
 
-                let mov = match (position, target) {
-                    (Ok(p), Ok(t)) => match self.get_entity_by_pos(p) {
-                        Some(e) => Some((e, t)),
-                        None => {
-                            println!("Illegal command, you are losing this move {:?}, {:?}", p, t);
-                            None
-                        }
-                    },
-                    _ => {
-                        println!("Illegal command, you are losing this move");
-                        None
-                    }
-                };
-                //clear buffers before using for next move
-                position_buffer.clear();
-                target_buffer.clear();
-                moves[i] = mov;
-                println!("--------");
-            }
-            //println!("{:?}", &moves);
+    fn emit(&mut self, event: SessionEvent) {
+        for observer in self.observers.iter_mut().flatten() {
+            observer.notify(&event);
+        }
+    }
+
+    /// End the game in favour of the opponent of `player`.
+    pub fn forfeit(&mut self, player: usize) {
+        self.result = SessionResult::Finished(Box::new(self.players[1 - player].clone()));
+        self.emit(SessionEvent::GameFinished {
+            result: self.result.clone(),
+        });
+    }
+
+    /// Get a reference to the session's result.
+    pub fn result(&self) -> &SessionResult {
+        &self.result
+    }
+
+    pub fn is_active(&self) -> bool {
+        matches!(self.result, SessionResult::Active)
+    }
 
-            maps[i] = self.submit_moves(moves);
+    /// Get a reference to the session's players.
+    pub fn players(&self) -> &[Player; 2] {
+        &self.players
+    }
+
+    /// Get a reference to the session's record, one map per round.
+    pub fn record(&self) -> &[Map] {
+        &self.record
+    }
+
+    /// Check that the figures of the players' sets and of the starting
+    /// board can be told apart by their symbols.
+    pub fn check_symbols(&self) -> Result<(), SymbolError> {
+        let sets = self.players.iter().flat_map(|p| p.set().iter());
+        figures::check_symbols(sets.chain(self.record[0].entities().map(|e| &e.figure)))
+    }
+
+    /// Resolve a round from both players' moves, observers are notified
+    /// of everything that happened.
+    pub fn play_round(&mut self, moves: [Moves; 2]) -> Vec<RoundEvent> {
+        for (player, moves) in moves.iter().enumerate() {
+            self.emit(SessionEvent::MoveSubmitted {
+                player,
+                moves: moves.clone(),
+            });
         }
-        let new_map = self.resolve_maps(&maps[0], &maps[1]);
-        self.record.push(new_map);
-        self.show()
+        let (new_map, events) = engine::resolve_round(
+            &self.get_last_map(),
+            &moves[0],
+            &moves[1],
+            [self.players[0].id, self.players[1].id],
+            &self.rules,
+        );
+        for event in events.iter() {
+            self.emit(SessionEvent::Round(event.clone()));
+        }
+        self.push_round(new_map);
+        self.emit(SessionEvent::RoundEnded {
+            round: self.record.len() - 1,
+            map: Box::new(self.get_last_map()),
+        });
+        if self.is_over() {
+            self.emit(SessionEvent::GameFinished {
+                result: self.result.clone(),
+            });
+        }
+        events
     }
 
-    pub fn show(&self) {
-        let map = self.record.last().unwrap();
-        println!("{}", map);
+    /// Mark an active game as waiting for a player to come back.
+    pub fn suspend(&mut self) {
+        if self.is_active() {
+            self.result = SessionResult::Suspended;
+        }
     }
 
-    pub fn resolve_maps(&self, m1: &Map, m2: &Map) -> Map {
-        // m1.positions;
-        let map1 = m1;
-        let m0 = self.record.last().unwrap();
+    /// Continue a suspended game.
+    pub fn resume(&mut self) {
+        if matches!(self.result, SessionResult::Suspended) {
+            self.result = self.rules.judge(&self.record, &self.players);
+        }
+    }
 
-        let mut map2 = m2;
-        // map2.rotate90();
-        // map2.rotate90();
-        let mut resolved_map = m0.clone();
-        for i in 0..16 {
-            for j in 0..16 {
-                let (e0, e1, e2) = (
-                    m0.positions[i][j].clone(),
-                    map1.positions[i][j].clone(),
-                    map2.positions[i][j].clone(),
-                );
-
-                if e0 == None && e1 == None && e2 == None {
-                    resolved_map.positions[i][j] = None;
-                } else if e0 != None && e1 == None && e2 != None {
-                    resolved_map.positions[i][j] = e1;
-                } else if e0 != None && e1 != None && e2 == None {
-                    resolved_map.positions[i][j] = e2;
-                } else if e0 == None && e1 == None && e2 != None {
-                    resolved_map.positions[i][j] = e2;
-                } else if e0 == None && e1 != None && e2 == None {
-                    resolved_map.positions[i][j] = e1;
-                } else if e1 != None && e2 != None && e1 != e2 {
-                    let mut e1 = e1.unwrap();
-                    // println!("{:#?},{:#?}", &e1, &e2);
-                    let mut e2 = e2.unwrap();
-                    println!("Bitka pany! {} {}", &e1, &e2);
-                    if e1.points == e2.points {
-                        println!("Points are equal. Backing up.")
-                    } else if e1.points > e2.points {
-                        e2.deal_dmg(&mut e1);
-                        e2.state = State::Dead;
-                        resolved_map.positions[i][j] = Some(e1);
-                    } else if e1.points < e2.points {
-                        e1.deal_dmg(&mut e2);
-                        e1.state = State::Dead;
-                        resolved_map.positions[i][j] = Some(e2);
+    /// Whether the game ended with a winner or a draw.
+    pub fn is_over(&self) -> bool {
+        matches!(
+            self.result,
+            SessionResult::Finished(_) | SessionResult::Draw(_)
+        )
+    }
+
+    /// Hand in no moves for `player` in a commit-reveal round, for one who ran
+    /// out of time. The round is played once the opponent revealed.
+    pub fn forfeit_moves(&mut self, player: usize) -> Option<Vec<RoundEvent>> {
+        if self.commitments[player].is_none() {
+            self.commitments[player] = Some(Commitment::new(&[], ""));
+        }
+        self.revealed[player] = Some([None, None, None]);
+        if let [Some(m1), Some(m2)] = &self.revealed {
+            let moves = [m1.clone(), m2.clone()];
+            self.commitments = [None, None];
+            self.revealed = [None, None];
+            return Some(self.play_round(moves));
+        }
+        None
+    }
+
+    /// Commit to this round's moves without revealing them.
+    pub fn commit(&mut self, player: usize, commitment: Commitment) -> Result<(), CommitError> {
+        if self.commitments[player].is_some() {
+            return Err(CommitError::AlreadyCommitted);
+        }
+        self.commitments[player] = Some(commitment);
+        Ok(())
+    }
+
+    /// Reveal committed moves once both players committed, the round is
+    /// played when the second reveal arrives.
+    pub fn reveal(
+        &mut self,
+        player: usize,
+        squares: &[(Position, Position)],
+        nonce: &str,
+    ) -> Result<Option<Vec<RoundEvent>>, CommitError> {
+        let commitment = self.commitments[player].ok_or(CommitError::NotCommitted)?;
+        if self.commitments[1 - player].is_none() {
+            return Err(CommitError::OpponentNotCommitted);
+        }
+        if self.revealed[player].is_some() {
+            return Err(CommitError::AlreadyRevealed);
+        }
+        if !commitment.matches(squares, nonce) {
+            return Err(CommitError::Mismatch);
+        }
+        self.revealed[player] = Some(to_moves(&self.get_last_map(), squares));
+        if let [Some(m1), Some(m2)] = &self.revealed {
+            let moves = [m1.clone(), m2.clone()];
+            self.commitments = [None, None];
+            self.revealed = [None, None];
+            return Ok(Some(self.play_round(moves)));
+        }
+        Ok(None)
+    }
+
+    /// Append a resolved map to the record and judge the game, a suspended
+    /// game stays suspended unless it ended.
+    fn push_round(&mut self, map: Map) {
+        let suspended = matches!(self.result, SessionResult::Suspended);
+        self.record.push(map);
+        self.result = self.rules.judge(&self.record, &self.players);
+        if suspended {
+            self.suspend();
+        }
+    }
+    fn calculate_round(&mut self, controllers: &mut [Controller; 2]) {
+        let mov_num = self.rules.moves_per_round();
+        let mut moves: [Moves; 2] = Default::default();
+        let map = self.get_last_map();
+        for (i, controller) in controllers.iter_mut().enumerate() {
+            let player = &self.players[i];
+            // Under fog of war controllers only get what the player sees.
+            let view = match self.rules.fog() {
+                Some(vision) => map.view(player.id, vision),
+                None => map.clone(),
+            };
+            let started = Instant::now();
+            // Humans are stopped once out of time, bots are judged once
+            // they hand in.
+            let deadline = self.clock.allowance(i).map(|allowed| started + allowed);
+            moves[i] = match controller {
+                Controller::Human => {
+                    if let Some(vision) = self.rules.fog() {
+                        let visible = map.visibility(player.id, vision);
+                        println!("{}", Renderer::for_stdout().render_fogged(&view, &visible));
+                    }
+                    if let Some(allowance) = self.clock.allowance(i) {
+                        println!("P: {}; {:.1}s left", player.name(), allowance.as_secs_f64());
                     }
-                } else {
-                    continue;
+                    self.read_moves(player, mov_num, deadline)
+                }
+                Controller::Tui(tui) => match tui.read_moves(&view, player, mov_num, deadline) {
+                    Some(moves) => moves,
+                    None => return self.forfeit(i),
+                },
+                Controller::Bot(bot) => bot.choose_moves(&view, player, mov_num),
+            };
+            if !self.clock.charge(i, started.elapsed()) {
+                self.emit(SessionEvent::TimeExpired { player: i });
+                match self.clock.control().on_flag() {
+                    FlagPolicy::EmptyMoves => moves[i] = [None, None, None],
+                    FlagPolicy::Loss => return self.forfeit(i),
                 }
             }
         }
-        resolved_map
+        let died: Vec<Position> = self
+            .play_round(moves)
+            .into_iter()
+            .filter_map(|event| match event {
+                RoundEvent::EntityDied { entity } => Some(entity.position),
+                _ => None,
+            })
+            .collect();
+        self.show_round(&died)
     }
+
+    /// Read player's moves from standard input, moves not entered by
+    /// `deadline` are lost.
+    fn read_moves(&self, player: &Player, mov_num: usize, deadline: Option<Instant>) -> Moves {
+        // let mut moves: Vec<Option<(Entity, Position)>> = vec![];
+
+        let mut moves: Moves = [None, None, None];
+        let mut position_buffer = String::new();
+        let mut target_buffer = String::new();
+        let mut input = Timed::stdin(deadline);
+
+        for (i, slot) in moves.iter_mut().enumerate().take(mov_num) {
+            println!("P: {}; Move #{}", player.name(), &i + 1);
+
+            println!("Position: ");
+            if input.read_line(&mut position_buffer).is_err() {
+                println!("Out of time");
+                break;
+            }
+
+            println!("Move: {}", &position_buffer);
+            println!("Target: ");
+
+            if input.read_line(&mut target_buffer).is_err() {
+                println!("Out of time");
+                break;
+            }
+            println!("To: {}", &target_buffer);
+
+            position_buffer.pop();
+            let position = Position::from_str(&position_buffer);
+            let target = Position::from_str(&target_buffer);
+
+            let mov = match (position, target) {
+                (Ok(p), Ok(t)) => match self.get_entity_by_pos(p) {
+                    Some(e) => Some((e, t)),
+                    None => {
+                        println!("Illegal command, you are losing this move {:?}, {:?}", p, t);
+                        None
+                    }
+                },
+                _ => {
+                    println!("Illegal command, you are losing this move");
+                    None
+                }
+            };
+            //clear buffers before using for next move
+            position_buffer.clear();
+            target_buffer.clear();
+            *slot = mov;
+            println!("--------");
+        }
+        //println!("{:?}", &moves);
+        moves
+    }
+
+    pub fn show(&self) {
+        self.show_round(&[]);
+    }
+
+    /// Print the board, marking where entities died this round. Under fog of
+    /// war the board is only shown once the game is over.
+    fn show_round(&self, died: &[Position]) {
+        if self.rules.fog().is_some() && !self.is_over() {
+            return;
+        }
+        let map = self.record.last().unwrap();
+        println!("{}", Renderer::for_stdout().render(map, died));
+    }
+
+    #[allow(dead_code)]
     fn get_entity_by_id(&self, id: u8) -> Option<Entity> {
         let mut res: Option<Entity> = None;
         for row in self.get_last_map().positions {
             for item in row {
-                if item != None && item.clone().unwrap().id == id {
+                if item.is_some() && item.clone().unwrap().id == id {
                     res = item
                 }
             }
@@ -667,36 +971,14 @@ impl Session {
     }
     fn get_entity_by_pos(&self, position: Position) -> Option<Entity> {
         if position.is_valid() {
-            match self.get_last_map().positions[position.x as usize][position.y as usize].to_owned()
-            {
-                Some(e) => Some(e),
-                None => {
-                    print!("ree");
-                    None
-                }
-            }
+            self.get_last_map().positions[position.x as usize][position.y as usize].to_owned()
         } else {
-            print!("ee");
             None
         }
     }
     fn get_last_map(&self) -> Map {
         self.record.last().unwrap().to_owned()
     }
-    pub fn submit_moves(&mut self, moves: [Option<(Entity, Position)>; 3]) -> Map {
-        let mut map = self.record.last().unwrap().to_owned();
-        for mov in moves {
-            match mov {
-                Some((e, t)) => {
-                    map.move_entity(e.position, t);
-                }
-                None => {
-                    continue;
-                }
-            };
-        }
-        map
-    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bot::Bot;
+use crate::engine;
+use crate::eval::Weights;
+use crate::rng::Rng;
+use crate::{Map, Moves, Player};
+
+/// Monte Carlo tree search bot for simultaneous moves using decoupled UCT:
+/// at every node each side picks its own move list by UCB over its own
+/// statistics and the pair of choices leads to the child node.
+#[derive(Debug, Clone)]
+pub struct MctsBot {
+    weights: Weights,
+    rng: Rng,
+    playouts: usize,
+    threads: usize,
+    time_limit: Option<Duration>,
+    rollout_depth: usize,
+    max_candidates: usize,
+    exploration: f64,
+}
+
+/// Search parameters shared by every worker thread.
+#[derive(Debug, Clone)]
+struct Search<'a> {
+    weights: &'a Weights,
+    player: &'a Player,
+    opponent_id: Option<u128>,
+    mov_num: usize,
+    rollout_depth: usize,
+    max_candidates: usize,
+    exploration: f64,
+}
+
+#[derive(Debug, Default)]
+struct Arm {
+    visits: u32,
+    total: f64,
+}
+
+#[derive(Debug)]
+struct Node {
+    visits: u32,
+    ours: Vec<Moves>,
+    theirs: Vec<Moves>,
+    our_arms: Vec<Arm>,
+    their_arms: Vec<Arm>,
+    children: HashMap<(usize, usize), Node>,
+}
+
+impl MctsBot {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            weights: Weights::default(),
+            rng: Rng::new(seed),
+            playouts: 1000,
+            threads: 1,
+            time_limit: None,
+            rollout_depth: 4,
+            max_candidates: 24,
+            exploration: 1.4,
+        }
+    }
+
+    /// Get MctsBot scoring rollouts with given weights.
+    pub fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Get MctsBot running `playouts` playouts per round, split among threads.
+    pub fn with_playouts(mut self, playouts: usize) -> Self {
+        self.playouts = playouts.max(1);
+        self
+    }
+
+    /// Get MctsBot searching independent trees on `threads` threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Get MctsBot stopping the search once `limit` has passed.
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Visit counts of the bot's move lists at the root, summed over threads.
+    pub fn root_visits(&mut self, map: &Map, player: &Player, mov_num: usize) -> Vec<(Moves, u32)> {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let seeds: Vec<u64> = (0..self.threads).map(|_| self.rng.next_u64()).collect();
+        let search = Search {
+            weights: &self.weights,
+            player,
+            opponent_id: map
+                .entities()
+                .map(|e| e.owner_id)
+                .find(|id| *id != player.id),
+            mov_num,
+            rollout_depth: self.rollout_depth,
+            max_candidates: self.max_candidates,
+            exploration: self.exploration,
+        };
+        // The first threads run one more playout when they do not divide
+        // evenly.
+        let (share, extra) = (self.playouts / self.threads, self.playouts % self.threads);
+
+        let roots: Vec<Node> = thread::scope(|scope| {
+            let workers: Vec<_> = seeds
+                .into_iter()
+                .enumerate()
+                .map(|(i, seed)| {
+                    let search = search.clone();
+                    let playouts = share + usize::from(i < extra);
+                    scope.spawn(move || {
+                        let mut rng = Rng::new(seed);
+                        let mut root = Node::new(map, &search, &mut rng);
+                        for playout in 0..playouts {
+                            if playout > 0 && deadline.is_some_and(|d| Instant::now() >= d) {
+                                break;
+                            }
+                            root.playout(map, &search, &mut rng);
+                        }
+                        root
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        // Every root is built from the same map, so candidates only differ
+        // by sampling; merge visits by move list.
+        let mut merged: Vec<(Moves, u32)> = vec![];
+        for root in roots {
+            for (moves, arm) in root.ours.into_iter().zip(root.our_arms) {
+                match merged.iter_mut().find(|(m, _)| *m == moves) {
+                    Some((_, visits)) => *visits += arm.visits,
+                    None => merged.push((moves, arm.visits)),
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl Bot for MctsBot {
+    fn choose_moves(&mut self, map: &Map, player: &Player, mov_num: usize) -> Moves {
+        self.root_visits(map, player, mov_num)
+            .into_iter()
+            .max_by_key(|(_, visits)| *visits)
+            .map(|(moves, _)| moves)
+            .unwrap_or([None, None, None])
+    }
+}
+
+impl Node {
+    fn new(map: &Map, search: &Search, rng: &mut Rng) -> Self {
+        let ours = candidates(map, search.player.id, search, rng);
+        let theirs = match search.opponent_id {
+            Some(id) => candidates(map, id, search, rng),
+            None => vec![[None, None, None]],
+        };
+        Self {
+            visits: 0,
+            our_arms: ours.iter().map(|_| Arm::default()).collect(),
+            their_arms: theirs.iter().map(|_| Arm::default()).collect(),
+            ours,
+            theirs,
+            children: HashMap::new(),
+        }
+    }
+
+    /// Run one playout through this node, returns its value for the bot in `0..=1`.
+    fn playout(&mut self, map: &Map, search: &Search, rng: &mut Rng) -> f64 {
+        let i = select(&self.our_arms, self.visits, search.exploration, rng);
+        let j = select(&self.their_arms, self.visits, search.exploration, rng);
+        let (next, _) = engine::merge(
+            map,
+            &map.apply_moves(&self.ours[i], search.player.id),
+            &map.apply_moves(&self.theirs[j], search.opponent_id.unwrap_or_default()),
+        );
+
+        let value = if is_over(&next, search) {
+            rollout(&next, search, rng, 0)
+        } else {
+            match self.children.get_mut(&(i, j)) {
+                Some(child) => child.playout(&next, search, rng),
+                None => {
+                    let child = Node::new(&next, search, rng);
+                    self.children.insert((i, j), child);
+                    rollout(&next, search, rng, search.rollout_depth)
+                }
+            }
+        };
+
+        self.visits += 1;
+        self.our_arms[i].visits += 1;
+        self.our_arms[i].total += value;
+        self.their_arms[j].visits += 1;
+        self.their_arms[j].total += 1.0 - value;
+        value
+    }
+}
+
+/// UCB1 choice among arms, unvisited arms first.
+fn select(arms: &[Arm], visits: u32, exploration: f64, rng: &mut Rng) -> usize {
+    let unvisited: Vec<usize> = (0..arms.len()).filter(|i| arms[*i].visits == 0).collect();
+    if let Some(i) = rng.choose(&unvisited) {
+        return *i;
+    }
+    let log = (visits.max(1) as f64).ln();
+    let ucb =
+        |arm: &Arm| arm.total / arm.visits as f64 + exploration * (log / arm.visits as f64).sqrt();
+    (0..arms.len())
+        .max_by(|a, b| {
+            ucb(&arms[*a])
+                .partial_cmp(&ucb(&arms[*b]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+/// Passing plus a random sample of legal move lists of `owner_id`.
+fn candidates(map: &Map, owner_id: u128, search: &Search, rng: &mut Rng) -> Vec<Moves> {
+    let mut singles = map.legal_moves_of(owner_id);
+    let mut candidates: Vec<Moves> = vec![[None, None, None]];
+    while candidates.len() < search.max_candidates && !singles.is_empty() {
+        let (e, t) = singles.swap_remove(rng.below(singles.len()));
+        let mut moves: Moves = [Some((e, t)), None, None];
+        let mut scratch = map.apply_moves(&moves, owner_id);
+        for slot in moves.iter_mut().take(search.mov_num).skip(1) {
+            let next = scratch.legal_moves_of(owner_id);
+            if let Some((e, t)) = rng.choose(&next).cloned() {
+                scratch.move_entity(e.position, t);
+                *slot = Some((e, t));
+            }
+        }
+        candidates.push(moves);
+    }
+    candidates
+}
+
+fn random_moves(map: &Map, owner_id: u128, mov_num: usize, rng: &mut Rng) -> Moves {
+    let mut scratch = map.clone();
+    let mut moves: Moves = [None, None, None];
+    for slot in moves.iter_mut().take(mov_num) {
+        let next = scratch.legal_moves_of(owner_id);
+        if let Some((e, t)) = rng.choose(&next).cloned() {
+            scratch.move_entity(e.position, t);
+            *slot = Some((e, t));
+        }
+    }
+    moves
+}
+
+fn is_over(map: &Map, search: &Search) -> bool {
+    let alive = |id: u128| map.entities().any(|e| e.owner_id == id);
+    !alive(search.player.id) || search.opponent_id.is_some_and(|id| !alive(id))
+}
+
+/// Play `rounds` random rounds and score the result in `0..=1`.
+fn rollout(map: &Map, search: &Search, rng: &mut Rng, rounds: usize) -> f64 {
+    let mut map = map.clone();
+    for _ in 0..rounds {
+        if is_over(&map, search) {
+            break;
+        }
+        let ours = random_moves(&map, search.player.id, search.mov_num, rng);
+        let theirs = match search.opponent_id {
+            Some(id) => random_moves(&map, id, search.mov_num, rng),
+            None => [None, None, None],
+        };
+        let applied = [
+            map.apply_moves(&ours, search.player.id),
+            map.apply_moves(&theirs, search.opponent_id.unwrap_or_default()),
+        ];
+        map = engine::merge(&map, &applied[0], &applied[1]).0;
+    }
+    let score = search.weights.evaluate(&map, search.player);
+    1.0 / (1.0 + (-score / 32.0).exp())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::pawn;
+    use crate::testing::{place, player};
+    use crate::{Figure, FigureType, Position};
+
+    #[test]
+    fn test_picks_best_move_against_passive_opponent() {
+        let wall = Figure::new(
+            "Wall".to_string(),
+            'w',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        let mut map = Map::empty();
+        place(&mut map, 1, pawn(), 0, 0);
+        place(&mut map, 2, wall, 15, 15);
+        let weights = Weights {
+            material: 0.0,
+            special: 0.0,
+            threatened: 0.0,
+            mobility: 1.0,
+            leader_attacked: 0.0,
+            leader_lost: 0.0,
+            leader: "King".to_string(),
+        };
+        let mut bot = MctsBot {
+            rollout_depth: 0,
+            ..MctsBot::new(5).with_weights(weights).with_playouts(300)
+        };
+        let (_, target) = bot.choose_moves(&map, &player(1), 1)[0].clone().unwrap();
+        assert!(
+            target == Position::new(0, 2) || target == Position::new(2, 0),
+            "{}",
+            target
+        );
+    }
+
+    #[test]
+    fn test_moves_are_legal_and_reproducible() {
+        let map = Map::init(player(1), player(2));
+        let bot = MctsBot::new(11).with_playouts(60);
+        let (mut a, mut b) = (bot.clone(), bot);
+        let moves = a.choose_moves(&map, &player(1), 2);
+        assert_eq!(moves, b.choose_moves(&map, &player(1), 2));
+        let mut scratch = map.clone();
+        for (e, t) in moves.iter().flatten() {
+            assert_eq!(e.owner_id, 1);
+            assert!(scratch.is_legal(e, *t));
+            scratch.move_entity(e.position, *t);
+        }
+    }
+
+    #[test]
+    fn test_threads_share_playouts() {
+        let map = Map::init(player(1), player(2));
+        for playouts in [40, 10] {
+            let mut bot = MctsBot::new(2).with_playouts(playouts).with_threads(4);
+            let visits: u32 = bot
+                .root_visits(&map, &player(2), 1)
+                .iter()
+                .map(|(_, v)| v)
+                .sum();
+            assert_eq!(visits as usize, playouts);
+        }
+    }
+}
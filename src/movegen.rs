@@ -0,0 +1,189 @@
+use crate::{Entity, Map, Player, Position};
+
+impl Map {
+    /// Check whether the entity may move to target: on the board, free,
+    /// enterable, within its pattern and not blocked by anything on the way.
+    pub fn is_legal(&self, entity: &Entity, target: Position) -> bool {
+        target.is_valid()
+            && !self.is_taken(target)
+            && self.can_enter(entity, target)
+            && entity.can_reach(target)
+            && !self.is_blocked(entity.position, target)
+    }
+
+    /// Straight and diagonal moves are blocked by any entity or wall between
+    /// current and target, other moves jump.
+    pub fn is_blocked(&self, current: Position, target: Position) -> bool {
+        let dx = target.x as i8 - current.x as i8;
+        let dy = target.y as i8 - current.y as i8;
+        if !(dx == 0 || dy == 0 || dx.abs() == dy.abs()) {
+            return false;
+        }
+        let steps = dx.abs().max(dy.abs());
+        (1..steps).any(|step| {
+            let between = Position {
+                x: (current.x as i8 + dx.signum() * step) as u8,
+                y: (current.y as i8 + dy.signum() * step) as u8,
+            };
+            self.is_obstacle(between)
+        })
+    }
+
+    /// All squares the entity may legally move to.
+    pub fn legal_moves(&self, entity: &Entity) -> Vec<Position> {
+        let mut targets = vec![];
+        for dx in -4i8..5 {
+            for dy in -4i8..5 {
+                let x = entity.position.x as i8 + dx;
+                let y = entity.position.y as i8 + dy;
+                if !(0..16).contains(&x) || !(0..16).contains(&y) {
+                    continue;
+                }
+                let target = Position::new(x as u8, y as u8);
+                if self.is_legal(entity, target) {
+                    targets.push(target);
+                }
+            }
+        }
+        targets
+    }
+
+    /// All legal moves of the player's entities.
+    pub fn legal_moves_for(&self, player: &Player) -> Vec<(Entity, Position)> {
+        self.legal_moves_of(player.id)
+    }
+
+    /// All legal moves of entities owned by `owner_id`.
+    pub fn legal_moves_of(&self, owner_id: u128) -> Vec<(Entity, Position)> {
+        self.entities()
+            .filter(|e| e.owner_id == owner_id)
+            .flat_map(|e| self.legal_moves(e).into_iter().map(move |t| (e.clone(), t)))
+            .collect()
+    }
+
+    /// Squares the entity attacks: occupied by another owner's entity,
+    /// within its pattern and not blocked.
+    pub fn attacks(&self, entity: &Entity) -> Vec<Position> {
+        let mut targets = vec![];
+        for dx in -4i8..5 {
+            for dy in -4i8..5 {
+                let x = entity.position.x as i8 + dx;
+                let y = entity.position.y as i8 + dy;
+                if !(0..16).contains(&x) || !(0..16).contains(&y) {
+                    continue;
+                }
+                let target = Position::new(x as u8, y as u8);
+                let enemy = match &self.positions[target.x as usize][target.y as usize] {
+                    Some(other) => other.owner_id != entity.owner_id,
+                    None => false,
+                };
+                if enemy && entity.can_reach(target) && !self.is_blocked(entity.position, target) {
+                    targets.push(target);
+                }
+            }
+        }
+        targets
+    }
+
+    /// Number of attackers on every square, counting entities of owners
+    /// matching the filter.
+    pub fn attack_mask<F: Fn(u128) -> bool>(&self, attacker: F) -> [[u8; 16]; 16] {
+        let mut mask = [[0; 16]; 16];
+        for e in self.entities().filter(|e| attacker(e.owner_id)) {
+            for target in self.attacks(e) {
+                mask[target.x as usize][target.y as usize] += 1;
+            }
+        }
+        mask
+    }
+
+    /// Count sequences of `depth` consecutive legal moves of the player,
+    /// used to test move generation.
+    pub fn perft(&self, player: &Player, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves_for(player);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|(e, t)| {
+                let mut map = self.clone();
+                map.move_entity(e.position, t);
+                map.perft(player, depth - 1)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::{king, pawn, queen};
+    use crate::testing::{place, player};
+    use crate::Figure;
+
+    fn single(figure: Figure, x: u8, y: u8) -> Map {
+        let mut map = Map::empty();
+        place(&mut map, 1, figure, x, y);
+        map
+    }
+
+    #[test]
+    fn test_pattern_counts() {
+        assert_eq!(single(pawn(), 7, 7).perft(&player(1), 1), 8);
+        assert_eq!(single(king(), 7, 7).perft(&player(1), 1), 8);
+        assert_eq!(single(queen(), 7, 7).perft(&player(1), 1), 76);
+    }
+
+    #[test]
+    fn test_board_bounds() {
+        assert_eq!(single(pawn(), 0, 0).perft(&player(1), 1), 4);
+        assert_eq!(single(king(), 15, 15).perft(&player(1), 1), 3);
+        assert_eq!(single(queen(), 0, 0).perft(&player(1), 1), 22);
+    }
+
+    #[test]
+    fn test_occupied_and_blocked() {
+        let mut map = single(pawn(), 7, 7);
+        place(&mut map, 2, pawn(), 7, 8);
+        let e = map.positions[7][7].clone().unwrap();
+        let targets = map.legal_moves(&e);
+        assert_eq!(targets.len(), 6);
+        assert!(!targets.contains(&Position::new(7, 8)));
+        assert!(!targets.contains(&Position::new(7, 9)));
+    }
+
+    #[test]
+    fn test_attack_mask() {
+        let mut map = single(pawn(), 7, 7);
+        place(&mut map, 1, pawn(), 7, 5);
+        place(&mut map, 2, pawn(), 7, 8);
+        place(&mut map, 2, pawn(), 7, 9);
+        let mask = map.attack_mask(|owner_id| owner_id == 1);
+        assert_eq!(mask[7][8], 1);
+        assert_eq!(mask[7][9], 0, "blocked by the pawn on (7, 8)");
+        assert_eq!(mask[7][5], 0, "own entities are not attacked");
+        assert_eq!(map.attack_mask(|owner_id| owner_id == 2)[7][7], 1);
+    }
+
+    #[test]
+    fn test_perft_depth() {
+        assert_eq!(single(pawn(), 7, 7).perft(&player(1), 2), 64);
+        assert_eq!(single(pawn(), 0, 0).perft(&player(1), 2), 5 + 6 + 5 + 6);
+    }
+
+    #[test]
+    fn test_perft_opening() {
+        let map = Map::init(player(1), player(2));
+        assert_eq!(
+            map.perft(&player(1), 1),
+            map.perft(&player(2), 1),
+            "mirrored sets should have the same mobility"
+        );
+        assert_eq!(map.perft(&player(1), 0), 1);
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::rules::Rules;
+use crate::scenario::{position, terrain_lines, Scenario};
+use crate::{Map, Moves, Player, Position, PositionError, Session, SessionResult};
+
+/// Moves of a game stored as `(from, to)` squares, enough to replay it
+/// deterministically from the starting position.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GameRecord {
+    names: [String; 2],
+    /// Rules the game was played by, records without them are replayed by
+    /// the default rules.
+    rules: Option<Rules>,
+    /// Board the game started from if it is not the opening position.
+    start: Option<Map>,
+    rounds: Vec<[Vec<(Position, Position)>; 2]>,
+    result: String,
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    Position(usize, PositionError),
+    Syntax(usize),
+    Header(usize, String),
+}
+impl From<io::Error> for RecordError {
+    fn from(error: io::Error) -> Self {
+        RecordError::Io(error)
+    }
+}
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "{}", e),
+            RecordError::Position(line, e) => write!(f, "line {}: {:?}", line, e),
+            RecordError::Syntax(line) => write!(f, "line {}: unexpected content", line),
+            RecordError::Header(line, message) => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl GameRecord {
+    pub fn new(names: [String; 2]) -> Self {
+        Self {
+            names,
+            rules: None,
+            start: None,
+            rounds: vec![],
+            result: String::new(),
+        }
+    }
+
+    /// A record of `session` before its first round, with its players'
+    /// names, rules and starting board.
+    pub fn of(session: &Session) -> Self {
+        let [p1, p2] = session.players().clone();
+        let board = session.record()[0].clone();
+        let opening = Map::init(p1.clone(), p2.clone());
+        Self {
+            rules: Some(session.rules),
+            start: Some(board).filter(|board| *board != opening),
+            ..Self::new([p1.name().to_string(), p2.name().to_string()])
+        }
+    }
+
+    /// Append a round of moves submitted by both players.
+    pub fn push(&mut self, moves: &[Moves; 2]) {
+        let squares = |moves: &Moves| {
+            moves
+                .iter()
+                .flatten()
+                .map(|(e, t)| (e.position, *t))
+                .collect::<Vec<_>>()
+        };
+        self.rounds.push([squares(&moves[0]), squares(&moves[1])]);
+    }
+
+    /// Store how the game ended.
+    pub fn finish(&mut self, result: &SessionResult) {
+        self.result = result.to_string();
+    }
+
+    /// Get a reference to the record's player names.
+    pub fn names(&self) -> &[String; 2] {
+        &self.names
+    }
+
+    /// Number of rounds played.
+    pub fn len(&self) -> usize {
+        self.rounds.len()
+    }
+
+    /// Get the `(from, to)` squares of both players for every round.
+    pub fn rounds(&self) -> &[[Vec<(Position, Position)>; 2]] {
+        &self.rounds
+    }
+
+    /// Get a reference to the record's result.
+    pub fn result(&self) -> &str {
+        &self.result
+    }
+
+    /// Replay the game for the given players, calling `on_round` with the
+    /// session after every round.
+    pub fn replay<F: FnMut(&Session)>(&self, players: [Player; 2], mut on_round: F) -> Session {
+        // Older records do not store the rules, allow as many moves as the
+        // busiest round.
+        let rules = self.rules.unwrap_or_else(|| {
+            let mov_num = self
+                .rounds
+                .iter()
+                .flat_map(|round| round.iter().map(Vec::len))
+                .max()
+                .unwrap_or(1);
+            Rules::default().with_moves_per_round(mov_num)
+        });
+        let mut session = Session::new(players).with_rules(rules);
+        if let Some(start) = &self.start {
+            session = session.with_start(start.clone());
+        }
+        for round in self.rounds.iter() {
+            let map = session.get_last_map();
+            let moves = [to_moves(&map, &round[0]), to_moves(&map, &round[1])];
+            session.play_round(moves);
+            on_round(&session);
+        }
+        session
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RecordError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+/// Look up moved entities on the map the round started from.
+pub fn to_moves(map: &Map, squares: &[(Position, Position)]) -> Moves {
+    let mut moves: Moves = [None, None, None];
+    let mut scratch = map.clone();
+    for (slot, (from, to)) in moves.iter_mut().zip(squares) {
+        if let Some(e) = scratch.positions[from.x as usize][from.y as usize].clone() {
+            scratch.move_entity(*from, *to);
+            *slot = Some((e, *to));
+        }
+    }
+    moves
+}
+
+/// A header with the rules and the starting board as `start` followed by
+/// scenario lines, then one line per round:
+/// `round x y > x y, x y > x y | x y > x y`.
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# not_so_chess game record")?;
+        writeln!(f, "players {} | {}", self.names[0], self.names[1])?;
+        if let Some(rules) = &self.rules {
+            writeln!(f, "rules {}", rules)?;
+        }
+        if let Some(start) = &self.start {
+            for line in terrain_lines(start) {
+                writeln!(f, "start {}", line)?;
+            }
+            writeln!(f, "start position {}", position(start))?;
+        }
+        for round in self.rounds.iter() {
+            writeln!(
+                f,
+                "round {} | {}",
+                format_side(&round[0]),
+                format_side(&round[1])
+            )?;
+        }
+        writeln!(f, "result {}", self.result)
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = GameRecord::new([String::new(), String::new()]);
+        let mut start: Option<Scenario> = None;
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "players" => {
+                    let (p1, p2) = rest.split_once('|').ok_or(RecordError::Syntax(line_no))?;
+                    record.names = [p1.trim().to_string(), p2.trim().to_string()];
+                }
+                "round" => {
+                    let (m1, m2) = rest.split_once('|').ok_or(RecordError::Syntax(line_no))?;
+                    record
+                        .rounds
+                        .push([parse_side(m1, line_no)?, parse_side(m2, line_no)?]);
+                }
+                "rules" => {
+                    let rules = rest.parse().map_err(|e| RecordError::Header(line_no, e))?;
+                    record.rules = Some(rules);
+                }
+                "start" => start
+                    .get_or_insert_with(Scenario::default)
+                    .apply(line_no, rest, Path::new("."))
+                    .map_err(|e| RecordError::Header(line_no, e.to_string()))?,
+                "result" => record.result = rest.trim().to_string(),
+                _ => return Err(RecordError::Syntax(line_no)),
+            }
+        }
+        record.start = start.map(|scenario| scenario.map);
+        Ok(record)
+    }
+}
+
+/// Format moves of one side the way `parse_side` reads them.
+pub fn format_side(squares: &[(Position, Position)]) -> String {
+    squares
+        .iter()
+        .map(|(from, to)| format!("{} {} > {} {}", from.x, from.y, to.x, to.y))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parse moves of one side, `x y > x y` separated by commas.
+pub fn parse_side(s: &str, line_no: usize) -> Result<Vec<(Position, Position)>, RecordError> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|mov| !mov.is_empty())
+        .map(|mov| {
+            let (from, to) = mov.split_once('>').ok_or(RecordError::Syntax(line_no))?;
+            let parse = |p: &str| {
+                Position::from_str(p.trim()).map_err(|e| RecordError::Position(line_no, e))
+            };
+            Ok((parse(from)?, parse(to)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::bot::{Bot, RandomBot};
+    use crate::testing::players;
+
+    #[test]
+    fn test_round_trip_and_replay() {
+        let mut session =
+            Session::new(players()).with_rules(Rules::default().with_moves_per_round(2));
+        let mut record = GameRecord::new(["Ame".to_string(), "Gura".to_string()]);
+        let mut bots = [RandomBot::new(1), RandomBot::new(2)];
+        for _ in 0..10 {
+            let map = session.get_last_map();
+            let moves = [
+                bots[0].choose_moves(&map, &session.players()[0], 2),
+                bots[1].choose_moves(&map, &session.players()[1], 2),
+            ];
+            record.push(&moves);
+            session.play_round(moves);
+        }
+        record.finish(session.result());
+
+        let parsed: GameRecord = record.to_string().parse().unwrap();
+        assert_eq!(parsed, record);
+        let replayed = parsed.replay(players(), |_| {});
+        assert_eq!(replayed.record(), session.record());
+    }
+
+    #[test]
+    fn test_replay_from_stored_board_and_rules() {
+        let scenario: Scenario =
+            "terrain wall 1 1\nterrain fort 0 3\nposition 1 Queen 0 0, 2 Pawn 9 9"
+                .parse()
+                .unwrap();
+        let rules = Rules::unlimited().with_moves_per_round(2);
+        let mut session = scenario.session().with_rules(rules);
+        let mut record = GameRecord::of(&session);
+        let queen = session.get_last_map().positions[0][0].clone().unwrap();
+        let moves = [
+            [Some((queen, Position::new(0, 3))), None, None],
+            [None, None, None],
+        ];
+        record.push(&moves);
+        session.play_round(moves);
+
+        let written = record.to_string();
+        assert!(written.contains("rules moves 2 repetition off stagnation off draw fog off"));
+        assert!(written.contains("start terrain fort 0 3"));
+        let parsed: GameRecord = written.parse().unwrap();
+        assert_eq!(parsed, record);
+        let replayed = parsed.replay(players(), |_| {});
+        assert_eq!(replayed.rules, rules);
+        assert_eq!(replayed.record(), session.record());
+
+        // Games from the opening position do not store it.
+        assert_eq!(GameRecord::of(&Session::new(players())).start, None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "players a | b\nround 1 1 > x | ".parse::<GameRecord>(),
+            Err(RecordError::Position(2, _))
+        ));
+        assert!(matches!(
+            "moves 1".parse::<GameRecord>(),
+            Err(RecordError::Syntax(1))
+        ));
+        assert!(matches!(
+            "rules moves x".parse::<GameRecord>(),
+            Err(RecordError::Header(1, _))
+        ));
+    }
+}
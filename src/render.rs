@@ -0,0 +1,187 @@
+use std::io::{self, IsTerminal};
+
+use crate::fog::Visibility;
+use crate::terrain::Terrain;
+use crate::{Entity, Map, Position};
+
+const RESET: &str = "\x1b[0m";
+const DEAD: &str = "\x1b[2m";
+
+/// How boards are drawn: owners told apart by colour or, in plain ASCII, by
+/// case. Damaged entities carry a `*`, squares where entities died an `x`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Renderer {
+    colour: bool,
+    unicode: bool,
+}
+
+impl Renderer {
+    /// Plain ASCII: the first player's entities in upper case, the second's
+    /// in lower case.
+    pub fn plain() -> Self {
+        Self {
+            colour: false,
+            unicode: false,
+        }
+    }
+
+    /// ANSI colours and Unicode glyphs on a terminal, plain ASCII otherwise.
+    pub fn for_stdout() -> Self {
+        if io::stdout().is_terminal() {
+            Self {
+                colour: true,
+                unicode: true,
+            }
+        } else {
+            Self::plain()
+        }
+    }
+
+    /// The entity's symbol followed by its damage marker, two columns wide.
+    pub fn cell(&self, entity: &Entity) -> String {
+        let symbol = match glyph(entity) {
+            Some(glyph) if self.unicode => glyph.to_string(),
+            _ if self.colour => entity.get_symbol(),
+            _ if entity.owner_id == 1 => entity.get_symbol().to_uppercase(),
+            _ => entity.get_symbol().to_lowercase(),
+        };
+        let marker = if entity.points < entity.figure.full_points() {
+            '*'
+        } else {
+            ' '
+        };
+        match self.colour {
+            true => format!(
+                "{}{}{}{}",
+                owner_colour(entity.owner_id),
+                symbol,
+                RESET,
+                marker
+            ),
+            false => format!("{}{}", symbol, marker),
+        }
+    }
+
+    /// A square where an entity died, two columns wide.
+    fn grave(&self) -> String {
+        let symbol = if self.unicode { '✝' } else { 'x' };
+        match self.colour {
+            true => format!("{}{}{} ", DEAD, symbol, RESET),
+            false => format!("{} ", symbol),
+        }
+    }
+
+    /// An empty square of the given terrain, two columns wide.
+    pub fn ground(&self, terrain: Terrain) -> String {
+        let symbol = match (terrain, self.unicode) {
+            (Terrain::Plain, _) => '_',
+            (Terrain::Wall, true) => '█',
+            (Terrain::Wall, false) => '#',
+            (Terrain::Water, true) => '≈',
+            (Terrain::Water, false) => '~',
+            (Terrain::Fort, true) => '▲',
+            (Terrain::Fort, false) => '^',
+        };
+        format!("{} ", symbol)
+    }
+
+    /// The entity on the square or else its terrain, two columns wide.
+    pub fn square(&self, map: &Map, position: Position) -> String {
+        match &map.positions[position.x as usize][position.y as usize] {
+            Some(e) => self.cell(e),
+            None => self.ground(map.terrain(position)),
+        }
+    }
+
+    /// Draw `map` like its `Display`, marking `died` squares left empty.
+    pub fn render(&self, map: &Map, died: &[Position]) -> String {
+        self.draw(map, died, None)
+    }
+
+    /// Draw a player's view of the board, squares outside `visible` fogged.
+    pub fn render_fogged(&self, map: &Map, visible: &Visibility) -> String {
+        self.draw(map, &[], Some(visible))
+    }
+
+    fn draw(&self, map: &Map, died: &[Position], visible: Option<&Visibility>) -> String {
+        let fog = if self.unicode { "░ " } else { "? " };
+        let mut board = "==================================\n".to_string();
+        for y in (0..16).rev() {
+            board.push_str(&format!("{:X} ", y));
+            for x in 0..16 {
+                let square = Position::new(x, y);
+                match &map.positions[x as usize][y as usize] {
+                    _ if visible.is_some_and(|v| !v[x as usize][y as usize]) => board.push_str(fog),
+                    None if died.contains(&square) => board.push_str(&self.grave()),
+                    _ => board.push_str(&self.square(map, square)),
+                }
+            }
+            board.push('\n');
+        }
+        board.push_str("  0 1 2 3 4 5 6 7 8 9 A B C D E F\n=================================");
+        board
+    }
+}
+
+fn owner_colour(owner_id: u128) -> &'static str {
+    match owner_id {
+        1 => "\x1b[1;34m",
+        2 => "\x1b[1;31m",
+        _ => "\x1b[1;33m",
+    }
+}
+
+/// Glyph of the entity's figure, white for the first player.
+fn glyph(entity: &Entity) -> Option<char> {
+    let [white, black] = entity.figure.glyphs()?;
+    Some(if entity.owner_id == 1 { white } else { black })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::standard_set;
+    use crate::Player;
+
+    fn map() -> Map {
+        Map::init(
+            Player::new("Ame".to_string(), 1, standard_set()),
+            Player::new("Gura".to_string(), 2, standard_set()),
+        )
+    }
+
+    #[test]
+    fn test_plain_owners_and_damage() {
+        let mut map = map();
+        map.positions[0][2].as_mut().unwrap().points = 8;
+        map.set_terrain(Position::new(3, 2), Terrain::Wall);
+        map.set_terrain(Position::new(4, 2), Terrain::Water);
+        let board = Renderer::plain().render(&map, &[Position::new(5, 5)]);
+        let lines: Vec<&str> = board.lines().collect();
+        assert!(board.is_ascii());
+        assert_eq!(lines[1], "F _ _ _ _ _ _ _ _ _ _ _ _ _ p p p ");
+        assert_eq!(lines[11], "5 _ _ _ _ _ x _ _ _ _ _ _ _ _ _ _ ");
+        assert_eq!(lines[14], "2 P*K P # ~ _ _ _ _ _ _ _ _ _ _ _ ");
+    }
+
+    #[test]
+    fn test_colour_and_glyphs() {
+        let map = map();
+        let fancy = Renderer {
+            colour: true,
+            unicode: true,
+        };
+        let king = map.positions[1][2].as_ref().unwrap();
+        assert_eq!(fancy.cell(king), "\x1b[1;34m♔\x1b[0m ");
+        let queen = map.positions[13][14].as_ref().unwrap();
+        assert_eq!(fancy.cell(queen), "\x1b[1;31m♛\x1b[0m ");
+        assert!(fancy.render(&map, &[]).contains('♟'));
+
+        let mut visible = [[false; 16]; 16];
+        visible[0][2] = true;
+        let fogged = Renderer::plain().render_fogged(&map, &visible);
+        assert!(fogged.contains("\n2 P ? ? ? "));
+        assert!(!fogged.contains('p'));
+    }
+}
@@ -0,0 +1,366 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fog::Vision;
+use crate::{Map, Player, SessionResult};
+
+/// What happens once a game goes too many rounds without damage or death.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StagnationOutcome {
+    /// The game is declared a draw.
+    Draw,
+    /// The player with more points left on the board wins, equal points draw.
+    Adjudicate,
+}
+
+/// Why a session ended without a winner.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DrawReason {
+    /// The same position appeared the given number of times.
+    Repetition(usize),
+    /// The given number of rounds passed without damage or death.
+    Stagnation(usize),
+    /// Both players lost all their entities in the same round.
+    Annihilation,
+}
+
+/// Configurable rules a session is judged by.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rules {
+    moves_per_round: usize,
+    repetition_limit: Option<usize>,
+    stagnation_limit: Option<usize>,
+    stagnation_outcome: StagnationOutcome,
+    fog: Option<Vision>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            moves_per_round: 1,
+            repetition_limit: Some(3),
+            stagnation_limit: Some(50),
+            stagnation_outcome: StagnationOutcome::Draw,
+            fog: None,
+        }
+    }
+}
+
+impl Rules {
+    /// Rules without any draw conditions and the most moves per round.
+    pub fn unlimited() -> Self {
+        Self {
+            moves_per_round: 3,
+            repetition_limit: None,
+            stagnation_limit: None,
+            stagnation_outcome: StagnationOutcome::Draw,
+            fog: None,
+        }
+    }
+
+    /// Get Rules allowing `moves` moves per player and round, 1 to 3.
+    pub fn with_moves_per_round(mut self, moves: usize) -> Self {
+        self.moves_per_round = moves.clamp(1, 3);
+        self
+    }
+
+    /// Get Rules drawing on `limit`-fold repetition, `None` disables it.
+    pub fn with_repetition(mut self, limit: Option<usize>) -> Self {
+        self.repetition_limit = limit;
+        self
+    }
+
+    /// Get Rules ending the game after `limit` quiet rounds, `None` disables it.
+    pub fn with_stagnation(mut self, limit: Option<usize>, outcome: StagnationOutcome) -> Self {
+        self.stagnation_limit = limit;
+        self.stagnation_outcome = outcome;
+        self
+    }
+
+    /// Get Rules hiding what the opponent's entities do outside what the
+    /// player's entities see, `None` shows the whole board.
+    pub fn with_fog(mut self, vision: Option<Vision>) -> Self {
+        self.fog = vision;
+        self
+    }
+
+    /// Get the rules' moves_per_round.
+    pub fn moves_per_round(&self) -> usize {
+        self.moves_per_round
+    }
+
+    /// Get the rules' repetition_limit.
+    pub fn repetition_limit(&self) -> Option<usize> {
+        self.repetition_limit
+    }
+
+    /// Get the rules' stagnation_limit.
+    pub fn stagnation_limit(&self) -> Option<usize> {
+        self.stagnation_limit
+    }
+
+    /// Get the rules' stagnation_outcome.
+    pub fn stagnation_outcome(&self) -> StagnationOutcome {
+        self.stagnation_outcome
+    }
+
+    /// Get how far players see, `None` without fog of war.
+    pub fn fog(&self) -> Option<Vision> {
+        self.fog
+    }
+
+    /// Judge a game by its record, the last map being the current position.
+    pub fn judge(&self, record: &[Map], players: &[Player; 2]) -> SessionResult {
+        let current = match record.last() {
+            Some(map) => map,
+            None => return SessionResult::Active,
+        };
+        let alive = |player: &Player| current.entities().any(|e| e.owner_id == player.id);
+        match (alive(&players[0]), alive(&players[1])) {
+            (true, false) => return SessionResult::Finished(Box::new(players[0].clone())),
+            (false, true) => return SessionResult::Finished(Box::new(players[1].clone())),
+            (false, false) => return SessionResult::Draw(DrawReason::Annihilation),
+            (true, true) => {}
+        }
+        if let Some(limit) = self.repetition_limit {
+            let seen = record.iter().filter(|map| *map == current).count();
+            if limit > 0 && seen >= limit {
+                return SessionResult::Draw(DrawReason::Repetition(seen));
+            }
+        }
+        if let Some(limit) = self.stagnation_limit {
+            let quiet = quiet_rounds(record);
+            if limit > 0 && quiet >= limit {
+                return match self.stagnation_outcome {
+                    StagnationOutcome::Draw => SessionResult::Draw(DrawReason::Stagnation(quiet)),
+                    StagnationOutcome::Adjudicate => adjudicate(current, players, quiet),
+                };
+            }
+        }
+        SessionResult::Active
+    }
+}
+
+/// `moves N repetition N|off stagnation N|off draw|adjudicate fog VISION|off`,
+/// as stored with game records.
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = |limit: Option<usize>| match limit {
+            Some(limit) => limit.to_string(),
+            None => "off".to_string(),
+        };
+        write!(
+            f,
+            "moves {} repetition {} stagnation {} {} fog {}",
+            self.moves_per_round,
+            limit(self.repetition_limit),
+            limit(self.stagnation_limit),
+            match self.stagnation_outcome {
+                StagnationOutcome::Draw => "draw",
+                StagnationOutcome::Adjudicate => "adjudicate",
+            },
+            match self.fog {
+                Some(vision) => vision.to_string(),
+                None => "off".to_string(),
+            }
+        )
+    }
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    /// Parse rules written by `Display`, what is left out stays as by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+        let mut words = s.split_whitespace();
+        let limit = |word: Option<&str>| match word {
+            Some("off") => Ok(None),
+            Some(word) => word
+                .parse::<usize>()
+                .map(Some)
+                .map_err(|_| format!("invalid limit {}", word)),
+            None => Err("expected a limit".to_string()),
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "moves" => {
+                    let moves = words.next().and_then(|w| w.parse::<usize>().ok());
+                    rules = rules.with_moves_per_round(moves.ok_or("expected moves")?);
+                }
+                "repetition" => rules.repetition_limit = limit(words.next())?,
+                "stagnation" => rules.stagnation_limit = limit(words.next())?,
+                "draw" => rules.stagnation_outcome = StagnationOutcome::Draw,
+                "adjudicate" => rules.stagnation_outcome = StagnationOutcome::Adjudicate,
+                "fog" => {
+                    rules.fog = match words.next() {
+                        Some("off") => None,
+                        Some(vision) => Some(vision.parse()?),
+                        None => return Err("expected a vision".to_string()),
+                    }
+                }
+                _ => return Err(format!("unknown rule {}", word)),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Count trailing rounds in which no entity took damage or died.
+fn quiet_rounds(record: &[Map]) -> usize {
+    record
+        .windows(2)
+        .rev()
+        .take_while(|pair| {
+            let (before, after) = (&pair[0], &pair[1]);
+            before.entities().count() <= after.entities().count()
+                && total_points(before) <= total_points(after)
+        })
+        .count()
+}
+
+fn total_points(map: &Map) -> u32 {
+    map.entities().map(|e| e.points as u32).sum()
+}
+
+fn adjudicate(map: &Map, players: &[Player; 2], quiet: usize) -> SessionResult {
+    let p0 = map.points_of(players[0].id);
+    let p1 = map.points_of(players[1].id);
+    if p0 > p1 {
+        SessionResult::Finished(Box::new(players[0].clone()))
+    } else if p1 > p0 {
+        SessionResult::Finished(Box::new(players[1].clone()))
+    } else {
+        SessionResult::Draw(DrawReason::Stagnation(quiet))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::testing::place;
+    use crate::{Figure, FigureType, Position};
+
+    fn figure(fig_type: FigureType) -> Figure {
+        let mut pattern = [[0; 9]; 9];
+        pattern[3][4] = 1;
+        Figure::new(
+            "Pawn".to_string(),
+            'p',
+            pattern,
+            Position::new(0, 0),
+            fig_type,
+        )
+    }
+
+    fn players() -> [Player; 2] {
+        let set = [
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+            figure(FigureType::Normal),
+        ];
+        [
+            Player::new("Ame".to_string(), 1, set.clone()),
+            Player::new("Gura".to_string(), 2, set),
+        ]
+    }
+
+    fn start() -> Map {
+        let mut map = Map::empty();
+        place(&mut map, 1, figure(FigureType::Normal), 0, 0);
+        place(&mut map, 2, figure(FigureType::Normal), 15, 15);
+        map
+    }
+
+    #[test]
+    fn test_repetition_draw() {
+        let rules = Rules::unlimited().with_repetition(Some(3));
+        let mut moved = start();
+        moved.move_entity(Position::new(0, 0), Position::new(1, 0));
+        let record = vec![start(), moved.clone(), start(), moved, start()];
+        assert!(matches!(
+            rules.judge(&record, &players()),
+            SessionResult::Draw(DrawReason::Repetition(3))
+        ));
+        assert!(matches!(
+            rules.judge(&record[..4], &players()),
+            SessionResult::Active
+        ));
+    }
+
+    #[test]
+    fn test_stagnation_draw() {
+        let rules = Rules::unlimited().with_stagnation(Some(2), StagnationOutcome::Draw);
+        let record = vec![start(), start(), start()];
+        assert!(matches!(
+            rules.judge(&record, &players()),
+            SessionResult::Draw(DrawReason::Stagnation(2))
+        ));
+    }
+
+    #[test]
+    fn test_stagnation_resets_on_death() {
+        let rules = Rules::unlimited().with_stagnation(Some(2), StagnationOutcome::Draw);
+        let mut before = start();
+        place(&mut before, 2, figure(FigureType::Normal), 14, 15);
+        let mut lost = before.clone();
+        lost.positions[15][15] = None;
+        let record = vec![before.clone(), before, lost];
+        assert!(matches!(
+            rules.judge(&record, &players()),
+            SessionResult::Active
+        ));
+    }
+
+    #[test]
+    fn test_elimination() {
+        let rules = Rules::unlimited();
+        let mut map = start();
+        map.positions[0][0] = None;
+        match rules.judge(&[start(), map.clone()], &players()) {
+            SessionResult::Finished(winner) => assert_eq!(winner.name(), "Gura"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        map.positions[15][15] = None;
+        assert!(matches!(
+            rules.judge(&[start(), map], &players()),
+            SessionResult::Draw(DrawReason::Annihilation)
+        ));
+    }
+
+    #[test]
+    fn test_stagnation_adjudication() {
+        let rules = Rules::unlimited().with_stagnation(Some(1), StagnationOutcome::Adjudicate);
+        let mut map = start();
+        place(&mut map, 2, figure(FigureType::Special), 14, 15);
+        match rules.judge(&[map.clone(), map], &players()) {
+            SessionResult::Finished(winner) => assert_eq!(winner.name(), "Gura"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rules_round_trip() {
+        let rules = Rules::default()
+            .with_moves_per_round(3)
+            .with_stagnation(None, StagnationOutcome::Adjudicate)
+            .with_fog(Some(Vision::Radius(2)));
+        assert_eq!(
+            rules.to_string(),
+            "moves 3 repetition 3 stagnation off adjudicate fog radius:2"
+        );
+        assert_eq!(rules.to_string().parse(), Ok(rules));
+        assert_eq!(
+            Rules::unlimited().to_string().parse(),
+            Ok(Rules::unlimited())
+        );
+        assert_eq!("".parse(), Ok(Rules::default()));
+        assert!("moves 1 speed 2".parse::<Rules>().is_err());
+    }
+}
@@ -0,0 +1,522 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::figures::{by_name, load_figure, standard_set, FigureError};
+use crate::rules::Rules;
+use crate::terrain::Terrain;
+use crate::{Entity, Figure, Map, Player, Position, Session, State};
+
+/// What the player a scenario is about has to achieve.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Objective {
+    /// Every opposing entity of the named figure is gone.
+    Destroy(String),
+    /// Every opposing entity is gone.
+    Eliminate,
+    /// The player still has entities once the round limit is reached.
+    Survive,
+}
+
+/// How a puzzle stands after some rounds.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    Pending,
+    Solved,
+    Failed,
+}
+
+/// A starting position with a round limit and an objective for one player.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub names: [String; 2],
+    pub map: Map,
+    pub moves_per_round: usize,
+    pub rounds: usize,
+    /// Index of the player the objective is for.
+    pub player: usize,
+    pub objective: Objective,
+    /// Figures loaded from figure files with the files they came from.
+    figures: Vec<(String, Figure)>,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Syntax(usize, String),
+    Figure(String, FigureError),
+}
+impl From<io::Error> for ScenarioError {
+    fn from(error: io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "{}", e),
+            ScenarioError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            ScenarioError::Figure(file, e) => write!(f, "figure {}: {}", file, e),
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::Destroy(figure) => write!(f, "destroy {}", figure),
+            Objective::Eliminate => write!(f, "eliminate"),
+            Objective::Survive => write!(f, "survive"),
+        }
+    }
+}
+
+impl Objective {
+    /// Whether the objective of the player owning `owner_id` holds on `map`,
+    /// for `Survive` whether the player is still on the board.
+    pub fn met(&self, map: &Map, owner_id: u128) -> bool {
+        let mut opposing = map.entities().filter(|e| e.owner_id != owner_id);
+        match self {
+            Objective::Destroy(figure) => {
+                !opposing.any(|e| e.figure.name().eq_ignore_ascii_case(figure))
+            }
+            Objective::Eliminate => opposing.next().is_none(),
+            Objective::Survive => map.entities().any(|e| e.owner_id == owner_id),
+        }
+    }
+}
+
+impl Default for Scenario {
+    /// An empty board where the first player has to eliminate the second
+    /// within a round.
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            names: ["P1".to_string(), "P2".to_string()],
+            map: Map::empty(),
+            moves_per_round: 1,
+            rounds: 1,
+            player: 0,
+            objective: Objective::Eliminate,
+            figures: vec![],
+        }
+    }
+}
+
+impl Scenario {
+    /// Read a scenario file, figure files it names are looked up next to it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&fs::read_to_string(path)?, base)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ScenarioError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Parse a scenario, `base` is the directory figure files are relative to.
+    pub fn parse(s: &str, base: &Path) -> Result<Self, ScenarioError> {
+        let mut scenario = Scenario::default();
+        for (i, line) in s.lines().enumerate() {
+            scenario.apply(i + 1, line, base)?;
+        }
+        Ok(scenario)
+    }
+
+    /// Apply one line of a scenario file, `line_no` is reported in errors.
+    pub fn apply(&mut self, line_no: usize, line: &str, base: &Path) -> Result<(), ScenarioError> {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let syntax = |message: &str| ScenarioError::Syntax(line_no, message.to_string());
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = value.trim();
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let number = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|_| syntax("expected a number"))
+        };
+        match key {
+            "name" => self.name = value.to_string(),
+            "players" => match value.split_once('|') {
+                Some((n1, n2)) => self.names = [n1.trim().to_string(), n2.trim().to_string()],
+                None => return Err(syntax("players are NAME | NAME")),
+            },
+            "moves" => self.moves_per_round = number(value)?.clamp(1, 3),
+            "rounds" => self.rounds = number(value)?.max(1),
+            "figure" => {
+                let figure = load_figure(base.join(value))
+                    .map_err(|e| ScenarioError::Figure(value.to_string(), e))?;
+                self.figures.push((value.to_string(), figure));
+            }
+            "objective" => {
+                let (player, objective) = match words[..] {
+                    [player, "destroy", figure] => (player, Objective::Destroy(figure.to_string())),
+                    [player, "eliminate"] => (player, Objective::Eliminate),
+                    [player, "survive"] => (player, Objective::Survive),
+                    _ => {
+                        return Err(syntax(
+                            "objective is PLAYER destroy FIGURE | eliminate | survive",
+                        ))
+                    }
+                };
+                self.player = (parse_owner(player).map_err(syntax)? - 1) as usize;
+                self.objective = objective;
+            }
+            "entity" => {
+                let entity = self.entity(&words).map_err(syntax)?;
+                self.place(entity).map_err(syntax)?;
+            }
+            "terrain" => {
+                let terrain = words
+                    .first()
+                    .and_then(|kind| Terrain::parse(kind))
+                    .ok_or_else(|| syntax("terrain is plain, wall, water or fort"))?;
+                let squares = &words[1..];
+                if squares.is_empty() || squares.len() % 2 != 0 {
+                    return Err(syntax("terrain is KIND X Y [X Y ...]"));
+                }
+                for square in squares.chunks(2) {
+                    let position = parse_square(square[0], square[1]).map_err(syntax)?;
+                    let before = self.map.terrain(position);
+                    self.map.set_terrain(position, terrain);
+                    let stranded = self.map.positions[position.x as usize][position.y as usize]
+                        .as_ref()
+                        .is_some_and(|e| !self.map.can_enter(e, position));
+                    if stranded {
+                        self.map.set_terrain(position, before);
+                        return Err(syntax("the entity on the square cannot stand there"));
+                    }
+                }
+            }
+            "position" => {
+                for spec in value.split(',').filter(|spec| !spec.trim().is_empty()) {
+                    let words: Vec<&str> = spec.split_whitespace().collect();
+                    let entity = self.entity(&words).map_err(syntax)?;
+                    self.place(entity).map_err(syntax)?;
+                }
+            }
+            _ => return Err(syntax("unknown key")),
+        }
+        Ok(())
+    }
+
+    /// The figure called `name`, figures loaded from files first.
+    pub fn figure(&self, name: &str) -> Option<Figure> {
+        self.figures
+            .iter()
+            .map(|(_, figure)| figure)
+            .find(|figure| figure.name().eq_ignore_ascii_case(name))
+            .cloned()
+            .or_else(|| by_name(name))
+    }
+
+    /// Read an entity from `OWNER FIGURE X Y [POINTS]`, its id is the lowest
+    /// one no entity on the board has.
+    pub fn entity(&self, words: &[&str]) -> Result<Entity, &'static str> {
+        let (owner, name, x, y, points) = match words[..] {
+            [owner, name, x, y] => (owner, name, x, y, None),
+            [owner, name, x, y, points] => (owner, name, x, y, Some(points)),
+            _ => return Err("an entity is OWNER FIGURE X Y [POINTS]"),
+        };
+        let owner_id = parse_owner(owner)?;
+        let figure = self.figure(name).ok_or("unknown figure")?;
+        let position = parse_square(x, y)?;
+        let id = (0..=u8::MAX)
+            .find(|id| self.map.entities().all(|e| e.id != *id))
+            .ok_or("there are no entity ids left")?;
+        let mut entity = Entity::new(id, owner_id, figure, position);
+        if let Some(points) = points {
+            entity.points = parse_points(points)?;
+        }
+        Ok(entity)
+    }
+
+    /// Put `entity` on its square of the board unless the square is taken.
+    pub fn place(&mut self, entity: Entity) -> Result<(), &'static str> {
+        let (x, y) = (entity.position.x as usize, entity.position.y as usize);
+        if self.map.positions[x][y].is_some() {
+            return Err("the square is taken");
+        }
+        if !self.map.can_enter(&entity, entity.position) {
+            return Err("the figure cannot stand on the square's terrain");
+        }
+        self.map.positions[x][y] = Some(entity);
+        Ok(())
+    }
+
+    /// The board on one line, entities as in `entity` lines separated by
+    /// commas, the value of a `position` line.
+    pub fn position(&self) -> String {
+        position(&self.map)
+    }
+
+    /// Everything keeping the scenario from being played as a puzzle.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        for e in self.map.entities().filter(|e| e.state == State::Dead) {
+            problems.push(format!(
+                "{} at {} is dead, dead entities leave the board",
+                e.figure.name(),
+                e.position
+            ));
+        }
+        for owner_id in 1..=2 {
+            if !self.map.entities().any(|e| e.owner_id == owner_id) {
+                problems.push(format!("P{} has no entities", owner_id));
+            }
+        }
+        if let Err(e) = self.session().check_symbols() {
+            problems.push(e.to_string());
+        }
+        if problems.is_empty() && self.outcome_after(&self.map, 0) != Outcome::Pending {
+            problems.push("the objective is decided before the first round".to_string());
+        }
+        problems
+    }
+
+    /// A session starting from the scenario's board. Only elimination ends
+    /// it, the round limit is the scenario's to enforce.
+    pub fn session(&self) -> Session {
+        let [n1, n2] = self.names.clone();
+        Session::new([
+            Player::new(n1, 1, standard_set()),
+            Player::new(n2, 2, standard_set()),
+        ])
+        .with_rules(Rules::unlimited().with_moves_per_round(self.moves_per_round))
+        .with_start(self.map.clone())
+    }
+
+    /// How the puzzle stands after the rounds of `record`, its first map
+    /// being the scenario's board.
+    pub fn outcome(&self, record: &[Map]) -> Outcome {
+        let current = record.last().expect("a record starts with the board");
+        self.outcome_after(current, record.len() - 1)
+    }
+
+    /// How the puzzle stands on `current` after `played` rounds.
+    pub fn outcome_after(&self, current: &Map, played: usize) -> Outcome {
+        let owner_id = self.player as u128 + 1;
+        let alive = current.entities().any(|e| e.owner_id == owner_id);
+        match self.objective {
+            Objective::Survive if !alive => Outcome::Failed,
+            Objective::Survive if played >= self.rounds => Outcome::Solved,
+            Objective::Survive => Outcome::Pending,
+            _ if self.objective.met(current, owner_id) => Outcome::Solved,
+            _ if !alive || played >= self.rounds => Outcome::Failed,
+            _ => Outcome::Pending,
+        }
+    }
+}
+
+/// Owner id of the first or second player.
+pub fn parse_owner(word: &str) -> Result<u128, &'static str> {
+    match word {
+        "1" => Ok(1),
+        "2" => Ok(2),
+        _ => Err("the player is 1 or 2"),
+    }
+}
+
+/// A square on the board from its coordinates.
+pub fn parse_square(x: &str, y: &str) -> Result<Position, &'static str> {
+    match (x.parse::<u8>(), y.parse::<u8>()) {
+        (Ok(x), Ok(y)) if x < 16 && y < 16 => Ok(Position::new(x, y)),
+        (Ok(_), Ok(_)) => Err("the square is off the board"),
+        _ => Err("expected a number"),
+    }
+}
+
+/// Points of an entity, 1 to 255.
+pub fn parse_points(word: &str) -> Result<u8, &'static str> {
+    match word.parse::<u8>() {
+        Ok(0) | Err(_) => Err("points are 1 to 255"),
+        Ok(points) => Ok(points),
+    }
+}
+
+/// Entities of `map` as in `entity` lines separated by commas, the value of
+/// a `position` line.
+pub fn position(map: &Map) -> String {
+    map.entities()
+        .map(|e| {
+            format!(
+                "{} {} {} {} {}",
+                e.owner_id,
+                e.figure.name(),
+                e.position.x,
+                e.position.y,
+                e.points
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `terrain` lines for every kind of terrain on `map` but plain.
+pub fn terrain_lines(map: &Map) -> Vec<String> {
+    [Terrain::Wall, Terrain::Water, Terrain::Fort]
+        .iter()
+        .filter_map(|terrain| {
+            let squares: Vec<String> = (0..16)
+                .flat_map(|x| (0..16).map(move |y| Position::new(x, y)))
+                .filter(|square| map.terrain(*square) == *terrain)
+                .map(|square| format!("{} {}", square.x, square.y))
+                .collect();
+            match squares.is_empty() {
+                true => None,
+                false => Some(format!("terrain {} {}", terrain, squares.join(" "))),
+            }
+        })
+        .collect()
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    /// Parse a scenario, figure files are relative to the working directory.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scenario::parse(s, Path::new("."))
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# not_so_chess scenario")?;
+        writeln!(f, "name {}", self.name)?;
+        writeln!(f, "players {} | {}", self.names[0], self.names[1])?;
+        writeln!(f, "moves {}", self.moves_per_round)?;
+        writeln!(f, "rounds {}", self.rounds)?;
+        for (file, _) in self.figures.iter() {
+            writeln!(f, "figure {}", file)?;
+        }
+        writeln!(f, "objective {} {}", self.player + 1, self.objective)?;
+        for line in terrain_lines(&self.map) {
+            writeln!(f, "{}", line)?;
+        }
+        for e in self.map.entities() {
+            writeln!(
+                f,
+                "entity {} {} {} {} {}",
+                e.owner_id,
+                e.figure.name(),
+                e.position.x,
+                e.position.y,
+                e.points
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::testing::place;
+    use crate::FigureType;
+
+    const KING_HUNT: &str = "
+# The queen takes the king before it gets away.
+name King hunt
+players Ame | Gura
+rounds 2
+objective 1 destroy King
+terrain wall 8 9 9 8
+terrain water 8 8
+entity 1 Queen 7 7
+entity 2 King 9 9 16
+entity 2 Pawn 15 15
+";
+
+    #[test]
+    fn test_parse_and_write() {
+        let scenario: Scenario = KING_HUNT.parse().unwrap();
+        assert_eq!(scenario.name, "King hunt");
+        assert_eq!(scenario.names[1], "Gura");
+        assert_eq!(scenario.rounds, 2);
+        assert_eq!(scenario.objective, Objective::Destroy("King".to_string()));
+        assert_eq!(scenario.map.entities().count(), 3);
+        let king = scenario.map.positions[9][9].as_ref().unwrap();
+        assert_eq!((king.owner_id, king.points), (2, 16));
+        let mut ids: Vec<u8> = scenario.map.entities().map(|e| e.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(scenario.map.terrain(Position::new(9, 8)), Terrain::Wall);
+        assert_eq!(scenario.map.terrain(Position::new(8, 8)), Terrain::Water);
+
+        let written: Scenario = scenario.to_string().parse().unwrap();
+        assert_eq!(written.map, scenario.map);
+        assert_eq!(written.to_string(), scenario.to_string());
+
+        let taken = "entity 1 Pawn 0 0\nentity 2 Pawn 0 0";
+        assert!(matches!(
+            taken.parse::<Scenario>(),
+            Err(ScenarioError::Syntax(2, _))
+        ));
+        assert!(matches!(
+            "entity 1 Dragon 0 0".parse::<Scenario>(),
+            Err(ScenarioError::Syntax(1, _))
+        ));
+        let drowned = "terrain water 0 0\nentity 1 Pawn 0 0";
+        assert!(matches!(
+            drowned.parse::<Scenario>(),
+            Err(ScenarioError::Syntax(2, _))
+        ));
+    }
+
+    #[test]
+    fn test_outcome() {
+        let scenario: Scenario = KING_HUNT.parse().unwrap();
+        let session = scenario.session();
+        assert_eq!(session.get_last_map(), scenario.map);
+        assert_eq!(scenario.outcome(session.record()), Outcome::Pending);
+
+        let board = scenario.map.clone();
+        let mut taken = board.clone();
+        taken.positions[9][9] = None;
+        assert_eq!(
+            scenario.outcome(&[board.clone(), taken.clone()]),
+            Outcome::Solved
+        );
+        let late = [board.clone(), board.clone(), board.clone()];
+        assert_eq!(scenario.outcome(&late), Outcome::Failed);
+
+        let survive = Scenario {
+            objective: Objective::Survive,
+            player: 1,
+            ..scenario.clone()
+        };
+        assert_eq!(survive.objective.to_string(), "survive");
+        assert_eq!(survive.outcome(&[board.clone(), taken]), Outcome::Pending);
+        assert_eq!(survive.outcome(&late), Outcome::Solved);
+        let mut wiped = board.clone();
+        wiped.positions[9][9] = None;
+        wiped.positions[15][15] = None;
+        assert_eq!(survive.outcome(&[board, wiped]), Outcome::Failed);
+    }
+
+    #[test]
+    fn test_symbol_clash_is_a_problem() {
+        let mut scenario: Scenario = KING_HUNT.parse().unwrap();
+        assert!(scenario.problems().is_empty());
+        let priest = Figure::new(
+            "Priest".to_string(),
+            'P',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        place(&mut scenario.map, 1, priest, 0, 0);
+        assert_eq!(
+            scenario.problems(),
+            vec!["Pawn and Priest share the symbol P".to_string()]
+        );
+    }
+}
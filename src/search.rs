@@ -0,0 +1,326 @@
+use std::time::{Duration, Instant};
+
+use crate::bot::Bot;
+use crate::engine;
+use crate::eval::Weights;
+use crate::rng::Rng;
+use crate::{Map, Moves, Player};
+
+/// Bot treating every round as a zero-sum matrix game: rows are its own
+/// candidate move lists, columns the opponent's, payoffs the evaluation of
+/// the resolved map. The game is solved with regret matching and the
+/// submission is sampled from the resulting mixed strategy.
+#[derive(Debug, Clone)]
+pub struct MatrixBot {
+    weights: Weights,
+    rng: Rng,
+    iterations: usize,
+    time_limit: Option<Duration>,
+    max_candidates: usize,
+}
+
+impl MatrixBot {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            weights: Weights::default(),
+            rng: Rng::new(seed),
+            iterations: 2000,
+            time_limit: None,
+            max_candidates: 16,
+        }
+    }
+
+    /// Get MatrixBot scoring positions with given weights.
+    pub fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Get MatrixBot stopping the search once `limit` has passed.
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Get MatrixBot running `iterations` rounds of regret matching.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Mixed strategy over the bot's candidate move lists. Under a time limit
+    /// rows of the payoff matrix not filled in time are left out, passing
+    /// always makes it in.
+    pub fn strategy(&mut self, map: &Map, player: &Player, mov_num: usize) -> Vec<(Moves, f64)> {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let opponent_id = map
+            .entities()
+            .map(|e| e.owner_id)
+            .find(|id| *id != player.id);
+
+        let weights = self.weights.clone();
+        let mut ours = self.candidates(map, player.id, mov_num, deadline, |resolved| {
+            weights.evaluate(resolved, player)
+        });
+        let theirs = match opponent_id {
+            Some(id) => self.candidates(map, id, mov_num, deadline, |resolved| {
+                -weights.evaluate(resolved, player)
+            }),
+            None => vec![[None, None, None]],
+        };
+
+        let their_id = opponent_id.unwrap_or_default();
+        let mut payoff: Vec<Vec<f64>> = vec![];
+        for (row, mine) in ours.iter().enumerate() {
+            if row > 0 && deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            let m1 = map.apply_moves(mine, player.id);
+            payoff.push(
+                theirs
+                    .iter()
+                    .map(|other| {
+                        let (resolved, _) =
+                            engine::merge(map, &m1, &map.apply_moves(other, their_id));
+                        weights.evaluate(&resolved, player)
+                    })
+                    .collect(),
+            );
+        }
+        ours.truncate(payoff.len());
+
+        let (strategy, _) = regret_matching(&payoff, self.iterations, deadline);
+        ours.into_iter().zip(strategy).collect()
+    }
+
+    /// Promising move lists of `owner_id`, always including passing. Once
+    /// `deadline` passed only the move lists ranked so far are considered.
+    fn candidates<F: Fn(&Map) -> f64>(
+        &mut self,
+        map: &Map,
+        owner_id: u128,
+        mov_num: usize,
+        deadline: Option<Instant>,
+        score: F,
+    ) -> Vec<Moves> {
+        let mut ranked: Vec<(f64, Moves)> = vec![];
+        for (e, t) in map.legal_moves_of(owner_id) {
+            if !ranked.is_empty() && deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            let mut moves: Moves = [Some((e.clone(), t)), None, None];
+            let mut scratch = map.apply_moves(&moves, owner_id);
+            for slot in moves.iter_mut().take(mov_num).skip(1) {
+                let next = scratch.legal_moves_of(owner_id);
+                if let Some((e, t)) = self.rng.choose(&next).cloned() {
+                    scratch.move_entity(e.position, t);
+                    *slot = Some((e, t));
+                }
+            }
+            let applied = map.apply_moves(&moves, owner_id);
+            ranked.push((score(&engine::merge(map, &applied, map).0), moves));
+        }
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        let mut candidates: Vec<Moves> = vec![[None, None, None]];
+        candidates.extend(
+            ranked
+                .into_iter()
+                .map(|(_, moves)| moves)
+                .take(self.max_candidates - 1),
+        );
+        candidates
+    }
+}
+
+impl Bot for MatrixBot {
+    fn choose_moves(&mut self, map: &Map, player: &Player, mov_num: usize) -> Moves {
+        let strategy = self.strategy(map, player, mov_num);
+        let mut roll = (self.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        for (moves, probability) in strategy.iter() {
+            if roll < *probability {
+                return moves.clone();
+            }
+            roll -= probability;
+        }
+        strategy
+            .last()
+            .map(|(moves, _)| moves.clone())
+            .unwrap_or([None, None, None])
+    }
+}
+
+/// Approximate a Nash equilibrium of the zero-sum game where the row player
+/// maximises `payoff[row][column]`. Returns average strategies of the row
+/// and the column player.
+pub fn regret_matching(
+    payoff: &[Vec<f64>],
+    iterations: usize,
+    deadline: Option<Instant>,
+) -> (Vec<f64>, Vec<f64>) {
+    let rows = payoff.len();
+    let columns = payoff.first().map(|row| row.len()).unwrap_or(0);
+    if rows == 0 || columns == 0 {
+        return (vec![1.0; rows.min(1)], vec![1.0; columns.min(1)]);
+    }
+    let mut row_regret = vec![0.0; rows];
+    let mut column_regret = vec![0.0; columns];
+    let mut row_sum = vec![0.0; rows];
+    let mut column_sum = vec![0.0; columns];
+
+    for iteration in 0..iterations.max(1) {
+        if iteration > 0 && deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+        // Play proportionally to positive regrets, uniformly if there are none.
+        let row_strategy = normalize(&row_regret);
+        let column_strategy = normalize(&column_regret);
+
+        let row_values: Vec<f64> = (0..rows)
+            .map(|r| {
+                (0..columns)
+                    .map(|c| payoff[r][c] * column_strategy[c])
+                    .sum()
+            })
+            .collect();
+        let column_values: Vec<f64> = (0..columns)
+            .map(|c| (0..rows).map(|r| -payoff[r][c] * row_strategy[r]).sum())
+            .collect();
+        let row_value: f64 = (0..rows).map(|r| row_values[r] * row_strategy[r]).sum();
+        let column_value: f64 = (0..columns)
+            .map(|c| column_values[c] * column_strategy[c])
+            .sum();
+
+        for r in 0..rows {
+            row_regret[r] = (row_regret[r] + row_values[r] - row_value).max(0.0);
+            row_sum[r] += row_strategy[r];
+        }
+        for c in 0..columns {
+            column_regret[c] = (column_regret[c] + column_values[c] - column_value).max(0.0);
+            column_sum[c] += column_strategy[c];
+        }
+    }
+    (normalize(&row_sum), normalize(&column_sum))
+}
+
+fn normalize(weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    if total > 0.0 {
+        weights.iter().map(|w| w / total).collect()
+    } else {
+        vec![1.0 / weights.len() as f64; weights.len()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::pawn;
+    use crate::testing::{place, player};
+    use crate::{Figure, FigureType, Position};
+
+    #[test]
+    fn test_rock_paper_scissors() {
+        let payoff = vec![
+            vec![0.0, -1.0, 1.0],
+            vec![1.0, 0.0, -1.0],
+            vec![-1.0, 1.0, 0.0],
+        ];
+        let (rows, columns) = regret_matching(&payoff, 20000, None);
+        for p in rows.iter().chain(columns.iter()) {
+            assert!((p - 1.0 / 3.0).abs() < 0.02, "{:?} {:?}", rows, columns);
+        }
+    }
+
+    #[test]
+    fn test_dominated_strategy() {
+        let payoff = vec![vec![3.0, 2.0], vec![1.0, 0.0]];
+        let (rows, columns) = regret_matching(&payoff, 5000, None);
+        assert!(rows[0] > 0.99);
+        assert!(columns[1] > 0.99);
+    }
+
+    #[test]
+    fn test_matching_pennies() {
+        let payoff = vec![vec![1.0, -1.0], vec![-1.0, 1.0]];
+        let (rows, _) = regret_matching(&payoff, 20000, None);
+        assert!((rows[0] - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_picks_best_move_against_passive_opponent() {
+        // The opponent only has an immobile wall, so the bot should simply
+        // pick the move leaving its pawn with the most mobility.
+        let wall = Figure::new(
+            "Wall".to_string(),
+            'w',
+            [[0; 9]; 9],
+            Position::new(0, 0),
+            FigureType::Normal,
+        );
+        let mut map = Map::empty();
+        place(&mut map, 1, pawn(), 0, 0);
+        place(&mut map, 2, wall, 15, 15);
+        let weights = Weights {
+            material: 0.0,
+            special: 0.0,
+            threatened: 0.0,
+            mobility: 1.0,
+            leader_attacked: 0.0,
+            leader_lost: 0.0,
+            leader: "King".to_string(),
+        };
+        for seed in 0..5 {
+            let mut bot = MatrixBot::new(seed).with_weights(weights.clone());
+            let moves = bot.choose_moves(&map, &player(1), 1);
+            let (_, target) = moves[0].clone().unwrap();
+            assert!(
+                target == Position::new(0, 2) || target == Position::new(2, 0),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_time_limit_bounds_payoffs() {
+        let map = Map::init(player(1), player(2));
+        let mut bot = MatrixBot::new(1)
+            .with_iterations(10)
+            .with_time_limit(Duration::from_secs(0));
+        // Out of time from the start, only passing is scored.
+        let strategy = bot.strategy(&map, &player(1), 2);
+        assert_eq!(strategy, vec![([None, None, None], 1.0)]);
+    }
+
+    #[test]
+    fn test_time_limit_bounds_candidates() {
+        let map = Map::init(player(1), player(2));
+        let limit = Duration::from_millis(20);
+        // Scoring every opening move takes several times the limit.
+        let mut bot = MatrixBot::new(1).with_iterations(10).with_time_limit(limit);
+        let start = Instant::now();
+        bot.strategy(&map, &player(1), 3);
+        let elapsed = start.elapsed();
+        assert!(elapsed < limit * 5, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_moves_are_legal_and_reproducible() {
+        let map = Map::init(player(1), player(2));
+        let bot = MatrixBot {
+            iterations: 200,
+            max_candidates: 6,
+            ..MatrixBot::new(3)
+        };
+        let (mut a, mut b) = (bot.clone(), bot);
+        let moves = a.choose_moves(&map, &player(2), 2);
+        assert_eq!(moves, b.choose_moves(&map, &player(2), 2));
+        let mut scratch = map.clone();
+        for (e, t) in moves.iter().flatten() {
+            assert_eq!(e.owner_id, 2);
+            assert!(scratch.is_legal(e, *t));
+            scratch.move_entity(e.position, *t);
+        }
+    }
+}
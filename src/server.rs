@@ -0,0 +1,1251 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::commit::{CommitError, Commitment};
+use crate::engine::RoundEvent;
+use crate::events::{SessionEvent, Spectator};
+use crate::fog::{either, is_seen, Vision};
+use crate::lobby::Lobby;
+use crate::record::{format_side, parse_side, to_moves};
+use crate::rng::Rng;
+use crate::rules::Rules;
+use crate::{Map, Moves, Session, SessionResult};
+
+/// How often finished and abandoned games are cleaned up.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a game waits for a player who lost the connection by default.
+const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+
+/// Hosts games for TCP clients speaking a line based protocol.
+///
+/// Client to server, in the lobby:
+/// - `LIST` list games
+/// - `CREATE <name>` open a new game
+/// - `JOIN <name>` take a seat in the oldest game waiting for a player
+/// - `JOIN <id> <name>` take a seat in the given game
+/// - `SPECTATE <id> <name>` watch the given game
+/// - `LEAVE` give up your seat before the game starts or stop watching
+/// - `RESUME <token>` take your seat in a started game again after losing
+///   the connection
+///
+/// Client to server, in a game:
+/// - `MOVE x y > x y, ...` submit this round's moves, `MOVE` alone passes
+/// - `COMMIT <hash>` commit-reveal games only, SHA-256 of `<nonce>:<moves>`
+///   with moves formatted as for `MOVE`
+/// - `REVEAL <nonce> x y > x y, ...` reveal committed moves once both committed
+/// - `QUIT` leave the game, forfeiting it once it started
+///
+/// Server to client:
+/// - `GAME <id> <name> <players>/2 <spectators> <waiting|playing>` one per game, then `END`
+/// - `CREATED <id>` the game is open
+/// - `WELCOME <seat> <id> <token>` seat 1 or 2, which is also the owner id of
+///   your entities, the token lets you `RESUME` after a disconnect
+/// - `WATCHING <id>` you receive the game's resolved rounds from now on
+/// - `WAITING` the opponent has not joined yet
+/// - `LEFT` you left the game
+/// - `START <name1> | <name2>` the game begins
+/// - `BOARD <owner> <symbol> <x> <y> <points>, ...` every entity on the board,
+///   under fog of war only those your entities see until the game is over
+/// - `ROUND <n>` moves for round n are expected
+/// - `SUBMITTED` your moves are stored, they stay hidden until both are in
+/// - `COMMITTED <seat> <hash>` a player committed to their moves
+/// - `REVEALED <seat> <nonce> <moves>` a player revealed matching moves
+/// - `SUSPENDED <seat>` the player lost the connection, the game waits for them,
+///   round timeouts count against them only once the reconnect grace ended,
+///   if both players stay away past it the one who left first forfeits
+/// - `RESUMED <seat>` the player is back, they get the board and round again
+/// - `TIMEOUT <seat>` the player did not submit in time, their moves are empty
+///   or they forfeit, depending on the server
+/// - `EVENT <text>` something that happened while resolving the round, under
+///   fog of war only what your entities saw
+/// - `RESOLVED <n>` spectators only, round n is over, sent after its events and board
+/// - `GAMEOVER <result>` the game is over, the connection is closed
+/// - `ERROR <message>` the last line was not understood
+pub struct Server {
+    listener: TcpListener,
+    rules: Rules,
+    shared: Arc<Mutex<Shared>>,
+    config: Config,
+}
+
+/// What happens to a player who has not submitted when a round times out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimeoutPolicy {
+    /// The player's moves for the round are empty.
+    ForfeitMoves,
+    /// The player loses the game, if both are late their moves are empty.
+    ForfeitGame,
+}
+
+/// How the server's games are played.
+#[derive(Debug, Clone, Copy)]
+struct Config {
+    commit_reveal: bool,
+    spectator_delay: usize,
+    round_timeout: Option<Duration>,
+    on_timeout: TimeoutPolicy,
+    reconnect_grace: Duration,
+}
+
+/// The lobby and the connections of every game in it.
+struct Shared {
+    lobby: Lobby,
+    tables: HashMap<u128, Table>,
+    rng: Rng,
+}
+
+/// Connections of a game and the channel its players' lines go through.
+struct Table {
+    players: [Option<TcpStream>; 2],
+    tokens: [Option<String>; 2],
+    spectators: Vec<Watcher>,
+    sender: Sender<Incoming>,
+    receiver: Option<Receiver<Incoming>>,
+}
+
+impl Table {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            players: [None, None],
+            tokens: [None, None],
+            spectators: vec![],
+            sender,
+            receiver: Some(receiver),
+        }
+    }
+
+    /// Whether `stream` is the connection currently in `seat`.
+    fn is_seated(&self, seat: usize, stream: &TcpStream) -> bool {
+        match &self.players[seat] {
+            Some(seated) => seated.peer_addr().ok() == stream.peer_addr().ok(),
+            None => false,
+        }
+    }
+}
+
+/// A spectator's connection and its observer, once the session started.
+/// Lines are queued for a writer thread of the spectator's own, so a slow
+/// connection never holds up the lock every game shares. `None` closes the
+/// connection once the lines before it are written.
+struct Watcher {
+    name: String,
+    lines: Sender<Option<String>>,
+    observer: Option<usize>,
+}
+
+impl Watcher {
+    fn new(name: String, stream: &TcpStream) -> io::Result<Self> {
+        let (lines, queued) = mpsc::channel::<Option<String>>();
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for line in queued {
+                match line {
+                    Some(line) => send(&mut writer, &line),
+                    None => {
+                        let _ = writer.shutdown(Shutdown::Both);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            name,
+            lines,
+            observer: None,
+        })
+    }
+
+    /// Queue a line for the spectator.
+    fn send(&self, line: &str) {
+        let _ = self.lines.send(Some(line.to_string()));
+    }
+
+    /// Close the connection after the queued lines.
+    fn hang_up(&self) {
+        let _ = self.lines.send(None);
+    }
+
+    /// Feed the session's resolved rounds to the spectator.
+    fn attach(&mut self, session: &mut Session, delay: usize) {
+        let lines = self.lines.clone();
+        let spectator = Spectator::new(delay, move |event: &SessionEvent| {
+            for line in spectator_lines(event) {
+                let _ = lines.send(Some(line));
+            }
+        });
+        self.observer = Some(session.subscribe(Box::new(spectator)));
+    }
+}
+
+/// Lines a spectator receives for an event of the session.
+fn spectator_lines(event: &SessionEvent) -> Vec<String> {
+    match event {
+        SessionEvent::Round(event) => vec![format!("EVENT {}", event)],
+        SessionEvent::RoundEnded { round, map } => {
+            vec![board_line(map), format!("RESOLVED {}", round)]
+        }
+        SessionEvent::GameFinished { result } => vec![format!("GAMEOVER {}", result)],
+        SessionEvent::TimeExpired { player } => vec![format!("TIMEOUT {}", player + 1)],
+        SessionEvent::MoveSubmitted { .. } => vec![],
+    }
+}
+
+/// What reaches a game's thread from its seats.
+enum Incoming {
+    Line(usize, String),
+    /// The seat's connection was lost.
+    Gone(usize),
+    /// The seat's player came back on a new connection.
+    Back(usize, TcpStream),
+}
+
+/// A played round's events, the map after it and the session's result.
+type Played = (Vec<RoundEvent>, Map, SessionResult);
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A, rules: Rules) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            rules,
+            shared: Arc::new(Mutex::new(Shared {
+                lobby: Lobby::new(rules),
+                tables: HashMap::new(),
+                rng: Rng::from_time(),
+            })),
+            config: Config {
+                commit_reveal: false,
+                spectator_delay: 0,
+                round_timeout: None,
+                on_timeout: TimeoutPolicy::ForfeitMoves,
+                reconnect_grace: RECONNECT_GRACE,
+            },
+        })
+    }
+
+    /// Get Server whose games take moves by commit and reveal instead of `MOVE`.
+    pub fn with_commit_reveal(mut self) -> Self {
+        self.config.commit_reveal = true;
+        self
+    }
+
+    /// Get Server showing spectators every round `rounds` rounds late.
+    pub fn with_spectator_delay(mut self, rounds: usize) -> Self {
+        self.config.spectator_delay = rounds;
+        self
+    }
+
+    /// Get Server giving players `timeout` to submit every round.
+    pub fn with_round_timeout(mut self, timeout: Duration, policy: TimeoutPolicy) -> Self {
+        self.config.round_timeout = Some(timeout);
+        self.config.on_timeout = policy;
+        self
+    }
+
+    /// Get Server waiting `grace` for players who lost the connection before
+    /// their round timeouts count.
+    pub fn with_reconnect_grace(mut self, grace: Duration) -> Self {
+        self.config.reconnect_grace = grace;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept clients forever, each one is served by its own thread and every
+    /// game is hosted by another.
+    pub fn run(&self) -> io::Result<()> {
+        if self.config.commit_reveal && self.rules.fog().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fog of war cannot hide moves revealed in commit-reveal games",
+            ));
+        }
+        let shared = self.shared.clone();
+        thread::spawn(move || loop {
+            thread::sleep(CLEANUP_INTERVAL);
+            let mut shared = shared.lock().unwrap();
+            for id in shared.lobby.cleanup(Instant::now()) {
+                shared.tables.remove(&id);
+            }
+        });
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let shared = self.shared.clone();
+            let config = self.config;
+            thread::spawn(move || {
+                if let Err(e) = serve_client(stream, shared, config) {
+                    eprintln!("Client ended with error: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Write a line to a client, one that went away is noticed by its reader.
+fn send(stream: &mut TcpStream, line: &str) {
+    let _ = stream.write_all(format!("{}\n", line).as_bytes());
+}
+
+/// Where a client is, outside of the lobby.
+enum Place {
+    Lobby,
+    Seat(u128, usize, String),
+    Watching(u128, String),
+}
+
+/// Handle a client's lobby commands, once its game started lines are handed
+/// over to the game's thread.
+fn serve_client(stream: TcpStream, shared: Arc<Mutex<Shared>>, config: Config) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut place = Place::Lobby;
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        let mut guard = shared.lock().unwrap();
+        let shared_state = &mut *guard;
+        if let Place::Seat(id, seat, _) = &place {
+            if shared_state.lobby.session(*id).is_some() {
+                match shared_state.tables.get(id) {
+                    Some(table) if table.is_seated(*seat, &writer) => {
+                        let _ = table.sender.send(Incoming::Line(*seat, line));
+                    }
+                    _ => send(&mut writer, "ERROR your seat was taken over"),
+                }
+                continue;
+            }
+        }
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (lobby, tables) = (&mut shared_state.lobby, &mut shared_state.tables);
+        match (command, &place) {
+            ("LIST", _) => {
+                for info in lobby.list() {
+                    send(&mut writer, &format!("GAME {}", info));
+                }
+                send(&mut writer, "END");
+            }
+            ("CREATE", _) if !rest.trim().is_empty() => {
+                let id = lobby.create(rest.trim());
+                send(&mut writer, &format!("CREATED {}", id));
+            }
+            ("JOIN", Place::Lobby) if !rest.trim().is_empty() => {
+                let (id, name) = match rest.trim().split_once(' ') {
+                    Some((id, name)) if id.parse::<u128>().is_ok() => {
+                        (id.parse().unwrap(), name.trim())
+                    }
+                    _ => (lobby.open_game(), rest.trim()),
+                };
+                match lobby.join(id, name) {
+                    Ok(seat) => {
+                        let token = format!("{:016x}", shared_state.rng.next_u64());
+                        let table = tables.entry(id).or_insert_with(Table::new);
+                        table.players[seat] = Some(writer.try_clone()?);
+                        table.tokens[seat] = Some(token.clone());
+                        send(
+                            &mut writer,
+                            &format!("WELCOME {} {} {}", seat + 1, id, token),
+                        );
+                        place = Place::Seat(id, seat, name.to_string());
+                        if let Some(session) = lobby.session_mut(id) {
+                            for watcher in table.spectators.iter_mut() {
+                                watcher.attach(session, config.spectator_delay);
+                            }
+                            let game = Game::new(shared.clone(), id, table, config)?;
+                            thread::spawn(move || game.host());
+                        } else {
+                            send(&mut writer, "WAITING");
+                        }
+                    }
+                    Err(e) => send(&mut writer, &format!("ERROR {}", e)),
+                }
+            }
+            ("SPECTATE", Place::Lobby) => {
+                let watched = rest.trim().split_once(' ').and_then(|(id, name)| {
+                    id.parse::<u128>()
+                        .ok()
+                        .map(|id| (id, name.trim().to_string()))
+                });
+                match watched {
+                    Some((id, name)) => match lobby.spectate(id, &name) {
+                        Ok(()) => {
+                            let mut watcher = Watcher::new(name.clone(), &writer)?;
+                            watcher.send(&format!("WATCHING {}", id));
+                            if let Some(session) = lobby.session_mut(id) {
+                                watcher.attach(session, config.spectator_delay);
+                            }
+                            let table = tables.entry(id).or_insert_with(Table::new);
+                            table.spectators.push(watcher);
+                            place = Place::Watching(id, name);
+                        }
+                        Err(e) => send(&mut writer, &format!("ERROR {}", e)),
+                    },
+                    None => send(&mut writer, "ERROR expected SPECTATE <id> <name>"),
+                }
+            }
+            ("RESUME", Place::Lobby) => {
+                let token = Some(rest.trim().to_string());
+                let seated = tables.iter().find_map(|(id, table)| {
+                    let seat = table.tokens.iter().position(|t| *t == token)?;
+                    let session = lobby.session(*id).filter(|s| !s.is_over())?;
+                    Some((*id, seat, session.players()[seat].name().to_string()))
+                });
+                match seated {
+                    Some((id, seat, name)) => {
+                        let table = tables.get_mut(&id).unwrap();
+                        table.players[seat] = Some(writer.try_clone()?);
+                        let _ = table.sender.send(Incoming::Back(seat, writer.try_clone()?));
+                        place = Place::Seat(id, seat, name);
+                    }
+                    None => send(&mut writer, "ERROR unknown token"),
+                }
+            }
+            ("LEAVE", Place::Seat(..)) | ("LEAVE", Place::Watching(..)) => {
+                leave(lobby, tables, &place);
+                place = Place::Lobby;
+                send(&mut writer, "LEFT");
+            }
+            ("QUIT", _) => break,
+            _ => send(&mut writer, "ERROR unexpected command"),
+        }
+    }
+
+    let mut guard = shared.lock().unwrap();
+    let shared_state = &mut *guard;
+    match &place {
+        Place::Seat(id, seat, _) if shared_state.lobby.session(*id).is_some() => {
+            if let Some(table) = shared_state.tables.get(id) {
+                if table.is_seated(*seat, &writer) {
+                    let _ = table.sender.send(Incoming::Gone(*seat));
+                }
+            }
+        }
+        _ => leave(&mut shared_state.lobby, &mut shared_state.tables, &place),
+    }
+    let _ = writer.shutdown(Shutdown::Both);
+    Ok(())
+}
+
+/// Leave a game that has not started yet or stop watching one.
+fn leave(lobby: &mut Lobby, tables: &mut HashMap<u128, Table>, place: &Place) {
+    let (id, name) = match place {
+        Place::Seat(id, _, name) | Place::Watching(id, name) => (*id, name),
+        Place::Lobby => return,
+    };
+    let _ = lobby.leave(id, name);
+    if let Some(table) = tables.get_mut(&id) {
+        if let Place::Seat(_, seat, _) = place {
+            table.players[*seat] = None;
+            table.tokens[*seat] = None;
+        }
+        for watcher in table.spectators.iter().filter(|w| w.name == *name) {
+            if let (Some(observer), Some(session)) = (watcher.observer, lobby.session_mut(id)) {
+                session.unsubscribe(observer);
+            }
+        }
+        table.spectators.retain(|watcher| watcher.name != *name);
+    }
+}
+
+struct Game {
+    id: u128,
+    shared: Arc<Mutex<Shared>>,
+    writers: Vec<TcpStream>,
+    incoming: Receiver<Incoming>,
+    config: Config,
+}
+
+impl Game {
+    fn new(
+        shared: Arc<Mutex<Shared>>,
+        id: u128,
+        table: &mut Table,
+        config: Config,
+    ) -> io::Result<Self> {
+        let mut writers = vec![];
+        for stream in table.players.iter().flatten() {
+            writers.push(stream.try_clone()?);
+        }
+        let incoming = table.receiver.take().expect("a game is started once");
+        Ok(Self {
+            id,
+            shared,
+            writers,
+            incoming,
+            config,
+        })
+    }
+
+    fn send(&mut self, seat: usize, line: &str) {
+        send(&mut self.writers[seat], line);
+    }
+
+    /// Write a line to both players.
+    fn broadcast(&mut self, line: &str) {
+        for stream in self.writers.iter_mut() {
+            send(stream, line);
+        }
+    }
+
+    /// Write a line to both players and every spectator, spectators only
+    /// see rounds through their observers otherwise.
+    fn announce(&mut self, line: &str) {
+        self.broadcast(line);
+        self.tell_spectators(line);
+    }
+
+    /// Write a line to every spectator.
+    fn tell_spectators(&mut self, line: &str) {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(table) = shared.tables.get_mut(&self.id) {
+            for watcher in table.spectators.iter() {
+                watcher.send(line);
+            }
+        }
+    }
+
+    /// Send the player in `seat` the board as their entities see it.
+    fn send_board(&mut self, seat: usize, map: &Map, fog: Option<Vision>) {
+        let line = match fog {
+            Some(vision) => board_line(&map.view(seat as u128 + 1, vision)),
+            None => board_line(map),
+        };
+        self.send(seat, &line);
+    }
+
+    /// Send each player the events of a round from `before` to `after` their
+    /// entities saw.
+    fn send_events(
+        &mut self,
+        events: &[RoundEvent],
+        before: &Map,
+        after: &Map,
+        fog: Option<Vision>,
+    ) {
+        for seat in 0..2 {
+            let owner_id = seat as u128 + 1;
+            let visible = fog.map(|vision| {
+                either(
+                    &before.visibility(owner_id, vision),
+                    &after.visibility(owner_id, vision),
+                )
+            });
+            for event in events {
+                if visible.as_ref().is_none_or(|v| is_seen(event, seat, v)) {
+                    self.send(seat, &format!("EVENT {}", event));
+                }
+            }
+        }
+    }
+
+    /// Run `f` on the game's session, finished sessions are only removed
+    /// by the lobby after this thread is done with them.
+    fn session<R, F: FnOnce(&mut Session) -> R>(&self, f: F) -> R {
+        let mut shared = self.shared.lock().unwrap();
+        f(shared
+            .lobby
+            .session_mut(self.id)
+            .expect("the session of a running game"))
+    }
+
+    /// Play a round with both players' moves.
+    fn play(&self, moves: [Moves; 2]) -> Played {
+        self.session(|s| {
+            let events = s.play_round(moves);
+            (events, s.get_last_map(), s.result().clone())
+        })
+    }
+
+    /// Close every connection of the game, which also stops their readers.
+    fn close(&mut self) {
+        for stream in self.writers.iter() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(table) = shared.tables.get_mut(&self.id) {
+            for watcher in table.spectators.drain(..) {
+                watcher.hang_up();
+            }
+        }
+    }
+
+    /// Wait for the next message from a seat until `deadline`, `None` once it
+    /// passed.
+    fn receive(&self, deadline: Option<Instant>) -> Option<Incoming> {
+        // The game holds a sender of its own channel through the table, so
+        // the channel is never disconnected while the game runs.
+        match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                match self.incoming.recv_timeout(left) {
+                    Ok(incoming) => Some(incoming),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => unreachable!("table holds a sender"),
+                }
+            }
+            None => Some(self.incoming.recv().expect("table holds a sender")),
+        }
+    }
+
+    fn host(mut self) {
+        let (names, mut map, fog) = self.session(|s| {
+            let players = s.players();
+            (
+                [players[0].name().to_string(), players[1].name().to_string()],
+                s.get_last_map(),
+                s.rules.fog(),
+            )
+        });
+        self.announce(&format!("START {} | {}", names[0], names[1]));
+        for seat in 0..2 {
+            self.send_board(seat, &map, fog);
+        }
+        self.tell_spectators(&board_line(&map));
+        let mut round = 1;
+        let mut result = SessionResult::Active;
+        // When each seat lost its connection, if it has not come back yet.
+        let mut gone: [Option<Instant>; 2] = [None, None];
+
+        while matches!(result, SessionResult::Active | SessionResult::Suspended) {
+            if round > 1 {
+                for seat in 0..2 {
+                    self.send_board(seat, &map, fog);
+                }
+            }
+            self.broadcast(&format!("ROUND {}", round));
+            let mut moves: [Option<Moves>; 2] = [None, None];
+            let mut committed = [false, false];
+            let mut revealed = [false, false];
+            let mut deadline = self.config.round_timeout.map(|t| Instant::now() + t);
+            let mut events = None;
+            while events.is_none() {
+                // Wake up for the round's deadline and for reconnect graces
+                // running out.
+                let wake = gone
+                    .iter()
+                    .flatten()
+                    .map(|since| *since + self.config.reconnect_grace)
+                    .filter(|end| *end > Instant::now())
+                    .chain(deadline)
+                    .min();
+                let (seat, line) = match self.receive(wake) {
+                    Some(Incoming::Line(seat, line)) => (seat, line),
+                    Some(Incoming::Gone(seat)) => {
+                        gone[seat] = Some(Instant::now());
+                        self.session(|s| s.suspend());
+                        self.broadcast(&format!("SUSPENDED {}", seat + 1));
+                        continue;
+                    }
+                    Some(Incoming::Back(seat, stream)) => {
+                        self.writers[seat] = stream;
+                        gone[seat] = None;
+                        if gone.iter().all(Option::is_none) {
+                            self.session(|s| s.resume());
+                        }
+                        deadline = self.config.round_timeout.map(|t| Instant::now() + t);
+                        self.broadcast(&format!("RESUMED {}", seat + 1));
+                        self.send_board(seat, &map, fog);
+                        self.send(seat, &format!("ROUND {}", round));
+                        if moves[seat].is_some() || committed[seat] {
+                            self.send(seat, "SUBMITTED");
+                        }
+                        continue;
+                    }
+                    None => {
+                        let now = Instant::now();
+                        let grace = self.config.reconnect_grace;
+                        if let [Some(first), Some(second)] = gone {
+                            if first.max(second) + grace <= now {
+                                // Nobody came back, the player who left first
+                                // forfeits the abandoned game.
+                                return self.forfeit(if first <= second { 0 } else { 1 });
+                            }
+                        }
+                        if deadline.is_none_or(|deadline| deadline > now) {
+                            continue;
+                        }
+                        // Nobody is late while the game waits for a player.
+                        let grace_end = gone
+                            .iter()
+                            .flatten()
+                            .map(|since| *since + grace)
+                            .filter(|end| *end > now)
+                            .min();
+                        if grace_end.is_some() {
+                            deadline = grace_end;
+                            continue;
+                        }
+                        let missing: Vec<usize> = (0..2)
+                            .filter(|&seat| match self.config.commit_reveal {
+                                true => {
+                                    !committed[seat] || (committed[1 - seat] && !revealed[seat])
+                                }
+                                false => moves[seat].is_none(),
+                            })
+                            .collect();
+                        for seat in missing.iter() {
+                            self.broadcast(&format!("TIMEOUT {}", seat + 1));
+                        }
+                        if self.config.on_timeout == TimeoutPolicy::ForfeitGame
+                            && missing.len() == 1
+                        {
+                            return self.forfeit(missing[0]);
+                        }
+                        for seat in missing {
+                            if self.config.commit_reveal {
+                                committed[seat] = true;
+                                revealed[seat] = true;
+                                if let Some(played) = self.session(|s| {
+                                    let played = s.forfeit_moves(seat)?;
+                                    Some((played, s.get_last_map(), s.result().clone()))
+                                }) {
+                                    events = Some(played);
+                                }
+                            } else {
+                                moves[seat] = Some([None, None, None]);
+                            }
+                        }
+                        if let [Some(m1), Some(m2)] = &moves {
+                            events = Some(self.play([m1.clone(), m2.clone()]));
+                        }
+                        deadline = self.config.round_timeout.map(|t| Instant::now() + t);
+                        continue;
+                    }
+                };
+                let line = line.trim();
+                let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+                match command {
+                    "MOVE" if self.config.commit_reveal => {
+                        self.send(seat, "ERROR expected COMMIT or REVEAL")
+                    }
+                    "MOVE" if moves[seat].is_some() => self.send(seat, "ERROR already submitted"),
+                    "MOVE" => match parse_side(rest, round) {
+                        Ok(squares) => {
+                            // Moves past the round's limit are rejected by the
+                            // engine, as they are when revealed.
+                            moves[seat] = Some(to_moves(&map, &squares));
+                            self.send(seat, "SUBMITTED");
+                            if let [Some(m1), Some(m2)] = &moves {
+                                events = Some(self.play([m1.clone(), m2.clone()]));
+                            }
+                        }
+                        Err(e) => self.send(seat, &format!("ERROR {}", e)),
+                    },
+                    "COMMIT" if self.config.commit_reveal => {
+                        let commitment = rest
+                            .parse()
+                            .and_then(|c| self.session(|s| s.commit(seat, c)).map(|_| c));
+                        match commitment {
+                            Ok(c) => {
+                                committed[seat] = true;
+                                self.broadcast(&format!("COMMITTED {} {}", seat + 1, c))
+                            }
+                            Err(e) => self.send(seat, &format!("ERROR {}", e)),
+                        }
+                    }
+                    "REVEAL" if self.config.commit_reveal => {
+                        let (nonce, squares) = rest.split_once(' ').unwrap_or((rest, ""));
+                        let played = parse_side(squares, round)
+                            .map_err(|e| e.to_string())
+                            .and_then(|squares| {
+                                self.session(|s| {
+                                    let played = s.reveal(seat, &squares, nonce)?;
+                                    Ok(played.map(|events| {
+                                        (events, s.get_last_map(), s.result().clone())
+                                    }))
+                                })
+                                .map_err(|e: CommitError| e.to_string())
+                            });
+                        match played {
+                            Ok(played) => {
+                                revealed[seat] = true;
+                                self.broadcast(&format!("REVEALED {} {}", seat + 1, rest));
+                                events = played;
+                            }
+                            Err(e) => self.send(seat, &format!("ERROR {}", e)),
+                        }
+                    }
+                    "QUIT" => return self.forfeit(seat),
+                    _ => self.send(seat, "ERROR unexpected command"),
+                }
+            }
+            let (events, next, next_result) = events.unwrap();
+            self.send_events(&events, &map, &next, fog);
+            map = next;
+            result = next_result;
+            round += 1;
+        }
+        self.broadcast(&board_line(&map));
+        self.broadcast(&format!("GAMEOVER {}", result));
+        self.close();
+    }
+
+    /// The player in `seat` quit or timed out, it loses and everyone else is told.
+    fn forfeit(mut self, seat: usize) {
+        self.session(|s| s.forfeit(seat));
+        self.broadcast(&format!("GAMEOVER forfeit {}", seat + 1));
+        self.close();
+    }
+}
+
+fn board_line(map: &Map) -> String {
+    let entities = map
+        .entities()
+        .map(|e| {
+            format!(
+                "{} {} {} {} {}",
+                e.owner_id,
+                e.figure.symbol(),
+                e.position.x,
+                e.position.y,
+                e.points
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("BOARD {}", entities.join(", "))
+}
+
+/// Connect to a server, printing what it sends and forwarding typed lines.
+/// `COMMIT` followed by moves commits to them with a fresh nonce and a bare
+/// `REVEAL` reveals the last committed moves.
+pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<()> {
+    let mut stream = TcpStream::connect(addr)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    thread::spawn(move || {
+        for line in reader.lines().map_while(Result::ok) {
+            println!("{}", line);
+        }
+        std::process::exit(0);
+    });
+    let mut rng = Rng::from_time();
+    let mut committed: Option<(String, String)> = None;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match (command, &committed) {
+            ("COMMIT", _) if rest.parse::<Commitment>().is_err() => match parse_side(rest, 0) {
+                Ok(squares) => {
+                    let nonce = format!("{:016x}", rng.next_u64());
+                    writeln!(stream, "COMMIT {}", Commitment::new(&squares, &nonce))?;
+                    committed = Some((nonce, format_side(&squares)));
+                }
+                Err(e) => println!("Invalid moves: {}", e),
+            },
+            ("REVEAL", Some((nonce, moves))) if rest.is_empty() => {
+                writeln!(stream, "REVEAL {} {}", nonce, moves)?
+            }
+            _ => writeln!(stream, "{}", line)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::Position;
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let writer = TcpStream::connect(addr).unwrap();
+            let reader = BufReader::new(writer.try_clone().unwrap());
+            Self { reader, writer }
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.writer, "{}", line).unwrap();
+        }
+
+        fn line(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            line.trim_end().to_string()
+        }
+
+        /// Read lines until one starts with `prefix`.
+        fn expect(&mut self, prefix: &str) -> String {
+            loop {
+                let line = self.line();
+                assert!(!line.is_empty(), "connection closed waiting for {}", prefix);
+                if line.starts_with(prefix) {
+                    return line;
+                }
+            }
+        }
+    }
+
+    fn spawn(server: Server) -> SocketAddr {
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run().unwrap());
+        addr
+    }
+
+    /// Seat two clients, returns them with their resume tokens.
+    fn start(server: Server) -> (Client, Client, [String; 2]) {
+        let addr = spawn(server);
+        let mut a = Client::connect(addr);
+        let mut b = Client::connect(addr);
+        a.send("JOIN Ame");
+        let first = a.line();
+        assert!(first.starts_with("WELCOME 1 1 "));
+        assert_eq!(a.line(), "WAITING");
+        b.send("JOIN Gura");
+        let second = b.line();
+        assert!(second.starts_with("WELCOME 2 1 "));
+        for client in [&mut a, &mut b].iter_mut() {
+            client.expect("START Ame | Gura");
+            assert!(client.line().starts_with("BOARD 1 "));
+            assert_eq!(client.line(), "ROUND 1");
+        }
+        let token = |line: String| line.rsplit(' ').next().unwrap().to_string();
+        (a, b, [token(first), token(second)])
+    }
+
+    #[test]
+    fn test_round_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::default()).unwrap();
+        let (mut a, mut b, _) = start(server);
+        // The pawn in the corner is free to step forward.
+        a.send("MOVE 0 2 > 0 3");
+        assert_eq!(a.line(), "SUBMITTED");
+        b.writer
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut peek = String::new();
+        assert!(b.reader.read_line(&mut peek).is_err(), "leaked {}", peek);
+        b.writer.set_read_timeout(None).unwrap();
+
+        b.send("MOVE 1 1 > nowhere");
+        assert!(b.line().starts_with("ERROR"));
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert!(client.expect("EVENT").contains("P1"));
+            assert!(client.expect("BOARD").contains("1 p 0 3 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+        a.send("QUIT");
+        assert_eq!(b.line(), "GAMEOVER forfeit 1");
+    }
+
+    #[test]
+    fn test_opponent_entities_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::default()).unwrap();
+        let (mut a, mut b, _) = start(server);
+        // The pawn on 15 13 is the second player's.
+        a.send("MOVE 15 13 > 15 12");
+        assert_eq!(a.line(), "SUBMITTED");
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert!(client.expect("EVENT").contains("(NotOwned)"));
+            assert!(client.expect("BOARD").contains("2 p 15 13 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+        a.send("QUIT");
+        assert_eq!(b.line(), "GAMEOVER forfeit 1");
+    }
+
+    #[test]
+    fn test_too_many_moves_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::default()).unwrap();
+        let (mut a, mut b, _) = start(server);
+        a.send("MOVE");
+        assert_eq!(a.line(), "SUBMITTED");
+        // One move a round, the second one is rejected as when revealed.
+        b.send("MOVE 15 13 > 15 12, 15 12 > 15 11");
+        assert_eq!(b.line(), "SUBMITTED");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert!(client.expect("EVENT").starts_with("EVENT P2: p"));
+            assert!(client.line().contains("(TooManyMoves)"));
+            assert!(client.expect("BOARD").contains("2 p 15 12 16"));
+        }
+
+        let rules = Rules::default().with_fog(Some(Vision::Pattern));
+        let server = Server::bind("127.0.0.1:0", rules)
+            .unwrap()
+            .with_commit_reveal();
+        assert_eq!(
+            server.run().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_fog_over_loopback() {
+        let rules = Rules::default().with_fog(Some(Vision::Radius(1)));
+        let addr = spawn(Server::bind("127.0.0.1:0", rules).unwrap());
+        let mut a = Client::connect(addr);
+        let mut b = Client::connect(addr);
+        a.send("JOIN Ame");
+        a.expect("WAITING");
+        b.send("JOIN Gura");
+        for (client, owner) in [(&mut a, "1 "), (&mut b, "2 ")].iter_mut() {
+            client.expect("START");
+            let board = client.line();
+            // The opponent's corner is out of sight.
+            let mut entities = board.trim_start_matches("BOARD ").split(", ");
+            assert!(entities.all(|e| e.starts_with(*owner)));
+            assert_eq!(client.line(), "ROUND 1");
+        }
+        a.send("MOVE 0 2 > 0 3");
+        assert_eq!(a.line(), "SUBMITTED");
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        assert!(a.line().starts_with("EVENT P1"));
+        assert!(a.line().contains("1 p 0 3 16"));
+        // The move was not seen, the next line is already the board.
+        assert!(b.line().starts_with("BOARD 2 "));
+        a.send("QUIT");
+        b.expect("GAMEOVER forfeit 1");
+    }
+
+    #[test]
+    fn test_commit_reveal_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_commit_reveal();
+        let (mut a, mut b, _) = start(server);
+        let ours = [(Position::new(0, 2), Position::new(0, 3))];
+        let commitment = Commitment::new(&ours, "n1");
+        a.send("MOVE 0 2 > 0 3");
+        assert!(a.line().starts_with("ERROR"));
+        a.send(&format!("COMMIT {}", commitment));
+        let committed = format!("COMMITTED 1 {}", commitment);
+        assert_eq!(a.line(), committed);
+        assert_eq!(b.line(), committed);
+        a.send("REVEAL n1 0 2 > 0 3");
+        assert!(a.line().starts_with("ERROR opponent"));
+
+        b.send(&format!("COMMIT {}", Commitment::new(&[], "n2")));
+        a.expect("COMMITTED 2");
+        b.expect("COMMITTED 2");
+        a.send("REVEAL n1 0 2 > 0 4");
+        assert_eq!(a.line(), "ERROR reveal does not match commitment");
+        a.send("REVEAL n1 0 2 > 0 3");
+        b.send("REVEAL n2");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert_eq!(client.expect("REVEALED 1"), "REVEALED 1 n1 0 2 > 0 3");
+            assert!(client.expect("BOARD").contains("1 p 0 3 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+        b.send("QUIT");
+        assert_eq!(a.line(), "GAMEOVER forfeit 2");
+    }
+
+    #[test]
+    fn test_lobby_commands() {
+        let addr = spawn(Server::bind("127.0.0.1:0", Rules::default()).unwrap());
+        let mut a = Client::connect(addr);
+        let mut b = Client::connect(addr);
+        let mut c = Client::connect(addr);
+        a.send("CREATE first");
+        assert_eq!(a.line(), "CREATED 1");
+        a.send("CREATE second");
+        assert_eq!(a.line(), "CREATED 2");
+        a.send("JOIN 2 Ame");
+        assert!(a.line().starts_with("WELCOME 1 2 "));
+        a.expect("WAITING");
+        a.send("LEAVE");
+        assert_eq!(a.line(), "LEFT");
+        a.send("JOIN 2 Ame");
+        a.expect("WAITING");
+
+        c.send("SPECTATE 2 Ina");
+        assert_eq!(c.line(), "WATCHING 2");
+        b.send("LIST");
+        assert_eq!(b.line(), "GAME 1 first 0/2 0 waiting");
+        assert_eq!(b.line(), "GAME 2 second 1/2 1 waiting");
+        assert_eq!(b.line(), "END");
+        b.send("JOIN 2 Ame");
+        assert_eq!(b.line(), "ERROR Ame is already in this game");
+        b.send("JOIN 2 Gura");
+        assert!(b.line().starts_with("WELCOME 2 2 "));
+        assert_eq!(c.line(), "START Ame | Gura");
+        assert!(c.line().starts_with("BOARD"));
+
+        b.send("QUIT");
+        assert_eq!(a.expect("GAMEOVER"), "GAMEOVER forfeit 2");
+        assert_eq!(c.line(), "GAMEOVER winner Ame");
+    }
+
+    #[test]
+    fn test_delayed_spectator_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::unlimited())
+            .unwrap()
+            .with_spectator_delay(1);
+        let addr = spawn(server);
+        let mut spectator = Client::connect(addr);
+        spectator.send("CREATE streamed");
+        assert_eq!(spectator.line(), "CREATED 1");
+        spectator.send("SPECTATE 1 Ina");
+        assert_eq!(spectator.line(), "WATCHING 1");
+        let mut a = Client::connect(addr);
+        let mut b = Client::connect(addr);
+        a.send("JOIN Ame");
+        b.send("JOIN Gura");
+        assert_eq!(spectator.line(), "START Ame | Gura");
+        assert!(spectator.line().starts_with("BOARD"));
+        for round in 1..=2 {
+            a.expect(&format!("ROUND {}", round));
+            b.expect(&format!("ROUND {}", round));
+            a.send(&format!("MOVE 0 {} > 0 {}", round + 1, round + 2));
+            b.send("MOVE");
+        }
+        // The first round is shown once the second is resolved.
+        a.expect("ROUND 3");
+        assert!(spectator.line().starts_with("EVENT P1"));
+        assert!(spectator.line().contains("1 p 0 3 16"));
+        assert_eq!(spectator.line(), "RESOLVED 1");
+
+        spectator
+            .writer
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut peek = String::new();
+        assert!(
+            spectator.reader.read_line(&mut peek).is_err(),
+            "leaked {}",
+            peek
+        );
+        spectator.writer.set_read_timeout(None).unwrap();
+
+        b.send("QUIT");
+        assert!(spectator.line().starts_with("EVENT P1"));
+        assert!(spectator.line().contains("1 p 0 4 16"));
+        assert_eq!(spectator.line(), "RESOLVED 2");
+        assert_eq!(spectator.line(), "GAMEOVER winner Ame");
+    }
+
+    #[test]
+    fn test_reconnect_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", Rules::default()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (mut a, mut b, tokens) = start(server);
+        a.send("MOVE 0 2 > 0 3");
+        assert_eq!(a.line(), "SUBMITTED");
+        drop(a);
+        assert_eq!(b.line(), "SUSPENDED 1");
+
+        let mut a = Client::connect(addr);
+        a.send("RESUME beef");
+        assert_eq!(a.line(), "ERROR unknown token");
+        a.send(&format!("RESUME {}", tokens[0]));
+        assert_eq!(a.line(), "RESUMED 1");
+        assert!(a.line().starts_with("BOARD 1 "));
+        assert_eq!(a.line(), "ROUND 1");
+        assert_eq!(a.line(), "SUBMITTED");
+        assert_eq!(b.line(), "RESUMED 1");
+
+        b.send("MOVE");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert!(client.expect("BOARD").contains("1 p 0 3 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+        a.send("QUIT");
+        assert_eq!(b.line(), "GAMEOVER forfeit 1");
+    }
+
+    #[test]
+    fn test_round_timeout_over_loopback() {
+        let timeout = Duration::from_millis(200);
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_round_timeout(timeout, TimeoutPolicy::ForfeitMoves);
+        let (mut a, mut b, _) = start(server);
+        a.send("MOVE 0 2 > 0 3");
+        assert_eq!(a.line(), "SUBMITTED");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert_eq!(client.line(), "TIMEOUT 2");
+            assert!(client.expect("BOARD").contains("1 p 0 3 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_round_timeout(timeout, TimeoutPolicy::ForfeitGame);
+        let (mut a, mut b, _) = start(server);
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        assert_eq!(a.line(), "TIMEOUT 1");
+        assert_eq!(a.line(), "GAMEOVER forfeit 1");
+        assert_eq!(b.line(), "TIMEOUT 1");
+        assert_eq!(b.line(), "GAMEOVER forfeit 1");
+    }
+
+    #[test]
+    fn test_abandoned_game_is_cleaned_up() {
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_reconnect_grace(Duration::from_millis(100));
+        let addr = server.local_addr().unwrap();
+        let (a, b, _) = start(server);
+        let mut spectator = Client::connect(addr);
+        spectator.send("SPECTATE 1 Ina");
+        assert_eq!(spectator.line(), "WATCHING 1");
+        drop(a);
+        thread::sleep(Duration::from_millis(20));
+        drop(b);
+        assert_eq!(spectator.line(), "GAMEOVER winner Gura");
+
+        thread::sleep(CLEANUP_INTERVAL * 2);
+        let mut c = Client::connect(addr);
+        c.send("LIST");
+        assert_eq!(c.line(), "END");
+    }
+
+    #[test]
+    fn test_timeout_during_reconnect_grace() {
+        let timeout = Duration::from_millis(100);
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_round_timeout(timeout, TimeoutPolicy::ForfeitGame)
+            .with_reconnect_grace(Duration::from_secs(5));
+        let addr = server.local_addr().unwrap();
+        let (a, mut b, tokens) = start(server);
+        drop(a);
+        assert_eq!(b.line(), "SUSPENDED 1");
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        // Several timeouts pass without the absent player losing.
+        thread::sleep(timeout * 4);
+
+        let mut a = Client::connect(addr);
+        a.send(&format!("RESUME {}", tokens[0]));
+        assert_eq!(a.line(), "RESUMED 1");
+        assert_eq!(b.line(), "RESUMED 1");
+        a.expect("ROUND 1");
+        a.send("MOVE 0 2 > 0 3");
+        assert_eq!(a.line(), "SUBMITTED");
+        for client in [&mut a, &mut b].iter_mut() {
+            assert!(client.expect("BOARD").contains("1 p 0 3 16"));
+            assert_eq!(client.line(), "ROUND 2");
+        }
+
+        // Once the grace ended the timeout counts again.
+        let server = Server::bind("127.0.0.1:0", Rules::default())
+            .unwrap()
+            .with_round_timeout(timeout, TimeoutPolicy::ForfeitGame)
+            .with_reconnect_grace(timeout * 2);
+        let (a, mut b, _) = start(server);
+        drop(a);
+        assert_eq!(b.line(), "SUSPENDED 1");
+        b.send("MOVE");
+        assert_eq!(b.line(), "SUBMITTED");
+        assert_eq!(b.line(), "TIMEOUT 1");
+        assert_eq!(b.line(), "GAMEOVER forfeit 1");
+    }
+}
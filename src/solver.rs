@@ -0,0 +1,238 @@
+use std::fmt;
+
+use crate::engine;
+use crate::record::format_side;
+use crate::scenario::{Outcome, Scenario};
+use crate::{Map, Moves};
+
+/// Rounds the solver resolves before giving up unless told otherwise.
+const NODE_LIMIT: usize = 10_000;
+
+/// Rounds of moves of both players, in session order.
+pub type Line = Vec<[Moves; 2]>;
+
+/// Whether the player a scenario is about can force its objective.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Verdict {
+    /// Some moves achieve the objective against every reply. The line plays
+    /// them against the replies holding out longest.
+    Forced(Line),
+    /// Every try fails against some reply. The line plays the try holding out
+    /// longest against the reply refuting it.
+    Refuted(Line),
+    /// The node limit was reached before the search finished.
+    Unknown,
+}
+
+/// Exhaustive search of a scenario where the player it is about commits to
+/// moves without knowing the opponent's, as both do every round.
+pub struct Solver<'a> {
+    scenario: &'a Scenario,
+    node_limit: usize,
+    nodes: usize,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(scenario: &'a Scenario) -> Self {
+        Self {
+            scenario,
+            node_limit: NODE_LIMIT,
+            nodes: 0,
+        }
+    }
+
+    /// Get Solver giving up after resolving `limit` rounds.
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = limit;
+        self
+    }
+
+    /// Get the number of rounds resolved so far.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Solve the scenario from its board.
+    pub fn solve(&mut self) -> Verdict {
+        let board = self.scenario.map.clone();
+        self.search(&board, 0)
+    }
+
+    fn search(&mut self, map: &Map, played: usize) -> Verdict {
+        match self.scenario.outcome_after(map, played) {
+            Outcome::Solved => return Verdict::Forced(vec![]),
+            Outcome::Failed => return Verdict::Refuted(vec![]),
+            Outcome::Pending => {}
+        }
+        let me = self.scenario.player;
+        let mov_num = self.scenario.moves_per_round;
+        let ours = options(map, me as u128 + 1, mov_num);
+        let theirs = options(map, 2 - me as u128, mov_num);
+        let mut best_try: Option<Line> = None;
+        'tries: for (mine, applied) in ours.iter() {
+            let mut hardest: Option<Line> = None;
+            for (reply, replied) in theirs.iter() {
+                if self.nodes >= self.node_limit {
+                    return Verdict::Unknown;
+                }
+                self.nodes += 1;
+                let (next, round) = match me {
+                    0 => (
+                        engine::merge(map, applied, replied).0,
+                        [mine.clone(), reply.clone()],
+                    ),
+                    _ => (
+                        engine::merge(map, replied, applied).0,
+                        [reply.clone(), mine.clone()],
+                    ),
+                };
+                match self.search(&next, played + 1) {
+                    Verdict::Forced(rest) => {
+                        if hardest
+                            .as_ref()
+                            .is_none_or(|line| rest.len() + 1 >= line.len())
+                        {
+                            hardest = Some(prepend(round, rest));
+                        }
+                    }
+                    Verdict::Refuted(rest) => {
+                        if best_try
+                            .as_ref()
+                            .is_none_or(|line| rest.len() + 1 >= line.len())
+                        {
+                            best_try = Some(prepend(round, rest));
+                        }
+                        continue 'tries;
+                    }
+                    Verdict::Unknown => return Verdict::Unknown,
+                }
+            }
+            return Verdict::Forced(hardest.unwrap_or_default());
+        }
+        Verdict::Refuted(best_try.unwrap_or_default())
+    }
+}
+
+fn prepend(round: [Moves; 2], rest: Line) -> Line {
+    let mut line = vec![round];
+    line.extend(rest);
+    line
+}
+
+/// Every way `owner_id` can play up to `mov_num` moves, one per map they lead
+/// to, passing first.
+fn options(map: &Map, owner_id: u128, mov_num: usize) -> Vec<(Moves, Map)> {
+    let mut options: Vec<(Moves, Map)> = vec![([None, None, None], map.clone())];
+    let mut frontier = 0;
+    for depth in 0..mov_num {
+        let layer = options[frontier..].to_vec();
+        frontier = options.len();
+        for (moves, applied) in layer {
+            for (e, t) in applied.legal_moves_of(owner_id) {
+                let mut next = applied.clone();
+                next.move_entity(e.position, t);
+                if options.iter().any(|(_, seen)| *seen == next) {
+                    continue;
+                }
+                let mut moves = moves.clone();
+                moves[depth] = Some((e, t));
+                options.push((moves, next));
+            }
+        }
+    }
+    options
+}
+
+/// One round per line, `x y > x y` for every move and `pass` for no moves.
+pub struct ShowLine<'a>(pub &'a Line);
+
+impl fmt::Display for ShowLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, round) in self.0.iter().enumerate() {
+            let side = |moves: &Moves| {
+                let squares: Vec<_> = moves
+                    .iter()
+                    .flatten()
+                    .map(|(e, t)| (e.position, *t))
+                    .collect();
+                match squares.is_empty() {
+                    true => "pass".to_string(),
+                    false => format_side(&squares),
+                }
+            };
+            writeln!(
+                f,
+                "round {}: {} | {}",
+                i + 1,
+                side(&round[0]),
+                side(&round[1])
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_options_lead_to_distinct_maps() {
+        let scenario: Scenario = "moves 2\nentity 1 Pawn 0 0\nentity 2 Pawn 9 9"
+            .parse()
+            .unwrap();
+        let ones = options(&scenario.map, 1, 1);
+        // Passing, two squares up and two to the right.
+        assert_eq!(ones.len(), 5);
+        assert_eq!(ones[0].0, [None, None, None]);
+        let twos = options(&scenario.map, 1, 2);
+        // Moving there and back again leads to the board, already passing.
+        assert!(twos.len() > ones.len());
+        let maps: Vec<&Map> = twos.iter().map(|(_, map)| map).collect();
+        for (i, map) in maps.iter().enumerate() {
+            assert!(!maps[i + 1..].contains(map));
+        }
+    }
+
+    #[test]
+    fn test_stationary_king_refutes_hunt() {
+        let scenario: Scenario =
+            "rounds 2\nobjective 1 destroy King\nentity 1 Queen 7 7\nentity 2 King 9 9"
+                .parse()
+                .unwrap();
+        let mut solver = Solver::new(&scenario);
+        let line = match solver.solve() {
+            Verdict::Refuted(line) => line,
+            verdict => panic!("expected a refutation, got {:?}", verdict),
+        };
+        // Entities only die meeting on a square, a king that stays put lives.
+        assert_eq!(line.len(), 2);
+        assert!(line.iter().all(|round| round[1] == [None, None, None]));
+        assert!(solver.nodes() > 0);
+
+        let mut limited = Solver::new(&scenario).with_node_limit(10);
+        assert_eq!(limited.solve(), Verdict::Unknown);
+        assert_eq!(limited.nodes(), 10);
+    }
+
+    #[test]
+    fn test_forced_survival() {
+        let scenario: Scenario =
+            "rounds 2\nobjective 2 survive\nentity 1 Queen 7 7\nentity 2 Pawn 9 9"
+                .parse()
+                .unwrap();
+        let line = match Solver::new(&scenario).solve() {
+            Verdict::Forced(line) => line,
+            verdict => panic!("expected a forced line, got {:?}", verdict),
+        };
+        assert_eq!(line.len(), 2);
+        assert!(line.iter().all(|round| round[1] == [None, None, None]));
+        let shown = ShowLine(&line).to_string();
+        assert!(shown.starts_with("round 1: "));
+        assert!(shown.contains(" | pass\nround 2: "));
+
+        let done: Scenario = "objective 1 eliminate\nentity 1 Pawn 0 0".parse().unwrap();
+        assert_eq!(Solver::new(&done).solve(), Verdict::Forced(vec![]));
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+
+use crate::{Entity, Map, Position};
+
+/// Damage the winner of a fight on a fort is spared.
+pub const FORT_DEFENCE: u8 = 8;
+
+/// What a square of the board is made of.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Terrain {
+    #[default]
+    Plain,
+    /// Blocks movement and line of sight.
+    Wall,
+    /// Only figures crossing water may enter it, of the standard set only
+    /// the queen does.
+    Water,
+    /// Spares the winner of a fight on it `FORT_DEFENCE` damage.
+    Fort,
+}
+
+impl Terrain {
+    /// Terrain by the name it is written with in scenario files.
+    pub fn parse(word: &str) -> Option<Terrain> {
+        match word {
+            "plain" => Some(Terrain::Plain),
+            "wall" => Some(Terrain::Wall),
+            "water" => Some(Terrain::Water),
+            "fort" => Some(Terrain::Fort),
+            _ => None,
+        }
+    }
+
+    /// Damage the winner of a fight on this terrain is spared.
+    pub fn defence(&self) -> u8 {
+        match self {
+            Terrain::Fort => FORT_DEFENCE,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Terrain::Plain => "plain",
+            Terrain::Wall => "wall",
+            Terrain::Water => "water",
+            Terrain::Fort => "fort",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Map {
+    /// Get the terrain of the square.
+    pub fn terrain(&self, position: Position) -> Terrain {
+        self.terrain[position.x as usize][position.y as usize]
+    }
+
+    /// Set the terrain of the square, an entity standing on it stays.
+    pub fn set_terrain(&mut self, position: Position, terrain: Terrain) {
+        self.terrain[position.x as usize][position.y as usize] = terrain;
+    }
+
+    /// Whether the entity may stand on the square: never on walls, on water
+    /// only if its figure crosses water.
+    pub fn can_enter(&self, entity: &Entity, position: Position) -> bool {
+        match self.terrain(position) {
+            Terrain::Wall => false,
+            Terrain::Water => entity.figure.crosses_water(),
+            Terrain::Plain | Terrain::Fort => true,
+        }
+    }
+
+    /// Whether the square stops movement and line of sight through it.
+    pub fn is_obstacle(&self, position: Position) -> bool {
+        self.is_taken(position) || self.terrain(position) == Terrain::Wall
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::engine::resolve_round;
+    use crate::figures::{king, parse_figure, pawn, queen};
+    use crate::rules::Rules;
+    use crate::testing::place;
+
+    #[test]
+    fn test_walls_and_water() {
+        let mut map = Map::empty();
+        let q = place(&mut map, 1, queen(), 7, 7);
+        let p = place(&mut map, 1, pawn(), 0, 0);
+        map.set_terrain(Position::new(8, 8), Terrain::Wall);
+        map.set_terrain(Position::new(0, 1), Terrain::Water);
+        map.set_terrain(Position::new(7, 9), Terrain::Water);
+        // Walls can not be entered or seen through, water only by the queen.
+        assert!(!map.is_legal(&q, Position::new(8, 8)));
+        assert!(!map.is_legal(&q, Position::new(9, 9)));
+        assert!(map.is_legal(&q, Position::new(7, 9)));
+        assert!(!map.is_legal(&p, Position::new(0, 1)));
+        let mut rows = [". . . . . . . . ."; 9];
+        rows[3] = ". . . . x . . . .";
+        rows[4] = ". . . . @ . . . .";
+        let frog = format!(
+            "name Frog\nsymbol f\ncrosses water\npattern\n{}",
+            rows.join("\n")
+        );
+        let swimmer = parse_figure(&frog).unwrap();
+        let f = place(&mut map, 1, swimmer, 0, 4);
+        map.set_terrain(Position::new(0, 5), Terrain::Water);
+        assert!(map.is_legal(&f, Position::new(0, 5)));
+        assert!(map.is_legal(&p, Position::new(1, 0)));
+
+        let target = place(&mut map, 2, pawn(), 10, 10);
+        assert!(!map.attacks(&q).contains(&target.position));
+        map.set_terrain(Position::new(8, 8), Terrain::Plain);
+        assert!(map.attacks(&q).contains(&target.position));
+    }
+
+    #[test]
+    fn test_fort_spares_damage() {
+        let mut map = Map::empty();
+        let k = place(&mut map, 1, king(), 5, 5);
+        let p = place(&mut map, 2, pawn(), 7, 6);
+        let meet = Position::new(6, 6);
+        let moves = |e: &Entity| [Some((e.clone(), meet)), None, None];
+        let rules = Rules::default();
+        let (plain, _) = resolve_round(&map, &moves(&k), &moves(&p), [1, 2], &rules);
+        assert_eq!(plain.positions[6][6].as_ref().unwrap().points, 16);
+
+        map.set_terrain(meet, Terrain::Fort);
+        let (fort, _) = resolve_round(&map, &moves(&k), &moves(&p), [1, 2], &rules);
+        assert_eq!(
+            fort.positions[6][6].as_ref().unwrap().points,
+            16 + FORT_DEFENCE
+        );
+        assert_eq!(fort.terrain(meet), Terrain::Fort);
+    }
+}
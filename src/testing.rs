@@ -0,0 +1,24 @@
+//! Helpers shared by the tests of several modules.
+
+use crate::figures::standard_set;
+use crate::{Entity, Figure, Map, Player, Position};
+
+/// A player with the standard set.
+pub fn player(id: u128) -> Player {
+    Player::new("Ame".to_string(), id, standard_set())
+}
+
+/// Both players of a game with the standard set.
+pub fn players() -> [Player; 2] {
+    [
+        Player::new("Ame".to_string(), 1, standard_set()),
+        Player::new("Gura".to_string(), 2, standard_set()),
+    ]
+}
+
+/// Put a fresh entity of `figure` on the square, returns it.
+pub fn place(map: &mut Map, owner_id: u128, figure: Figure, x: u8, y: u8) -> Entity {
+    let e = Entity::new(0, owner_id, figure, Position::new(x, y));
+    map.positions[x as usize][y as usize] = Some(e.clone());
+    e
+}
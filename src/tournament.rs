@@ -0,0 +1,206 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::bot::Bot;
+use crate::figures::standard_set;
+use crate::record::{GameRecord, RecordError};
+use crate::rules::Rules;
+use crate::{Player, Session, SessionResult};
+
+/// Elo rating every contestant starts with.
+const START_RATING: f64 = 1500.0;
+/// How fast ratings move after a single game.
+const K_FACTOR: f64 = 32.0;
+
+/// Summary of a tournament, contestants in the order they were configured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub names: [String; 2],
+    pub wins: [u32; 2],
+    pub draws: u32,
+    pub unfinished: u32,
+    pub rounds: usize,
+    pub ratings: [f64; 2],
+}
+
+impl Report {
+    pub fn games(&self) -> u32 {
+        self.wins[0] + self.wins[1] + self.draws + self.unfinished
+    }
+
+    pub fn average_length(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => self.rounds as f64 / games as f64,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games: {}", self.games())?;
+        for i in 0..2 {
+            writeln!(
+                f,
+                "{}: {} wins / {} draws / {} losses, rating {:.0}",
+                self.names[i],
+                self.wins[i],
+                self.draws,
+                self.wins[1 - i],
+                self.ratings[i]
+            )?;
+        }
+        if self.unfinished > 0 {
+            writeln!(f, "Unfinished: {}", self.unfinished)?;
+        }
+        write!(f, "Average length: {:.1} rounds", self.average_length())
+    }
+}
+
+/// Runs games between two bots without any terminal I/O.
+pub struct Tournament {
+    names: [String; 2],
+    bots: [Box<dyn Bot>; 2],
+    mov_num: usize,
+    max_rounds: usize,
+    out_dir: Option<PathBuf>,
+}
+
+impl Tournament {
+    pub fn new(names: [String; 2], bots: [Box<dyn Bot>; 2]) -> Self {
+        Self {
+            names,
+            bots,
+            mov_num: 1,
+            max_rounds: 500,
+            out_dir: None,
+        }
+    }
+
+    /// Get Tournament playing `mov_num` moves per round.
+    pub fn with_mov_num(mut self, mov_num: usize) -> Self {
+        self.mov_num = mov_num;
+        self
+    }
+
+    /// Get Tournament writing every game record into `dir`.
+    pub fn with_out_dir(mut self, dir: PathBuf) -> Self {
+        self.out_dir = Some(dir);
+        self
+    }
+
+    /// Play `games` games alternating sides, game records are written
+    /// to the output directory if there is one.
+    pub fn run(&mut self, games: u32) -> Result<Report, RecordError> {
+        if let Some(dir) = &self.out_dir {
+            fs::create_dir_all(dir)?;
+        }
+        let mut report = Report {
+            names: self.names.clone(),
+            wins: [0, 0],
+            draws: 0,
+            unfinished: 0,
+            rounds: 0,
+            ratings: [START_RATING, START_RATING],
+        };
+        for game in 0..games {
+            // Contestant sitting in seat 0 plays first listed player.
+            let seats = if game % 2 == 0 { [0, 1] } else { [1, 0] };
+            let (session, record) = self.play(seats);
+            report.rounds += record.len();
+            let score = match session.result() {
+                SessionResult::Finished(winner) => {
+                    let seat = if winner.id == session.players()[0].id {
+                        0
+                    } else {
+                        1
+                    };
+                    report.wins[seats[seat]] += 1;
+                    Some(if seats[seat] == 0 { 1.0 } else { 0.0 })
+                }
+                SessionResult::Draw(_) => {
+                    report.draws += 1;
+                    Some(0.5)
+                }
+                _ => {
+                    report.unfinished += 1;
+                    None
+                }
+            };
+            if let Some(score) = score {
+                report.ratings = update_ratings(report.ratings, score);
+            }
+            if let Some(dir) = &self.out_dir {
+                let path = dir.join(format!("game_{:04}.txt", game + 1));
+                record.save(path)?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn play(&mut self, seats: [usize; 2]) -> (Session, GameRecord) {
+        let names = [self.names[seats[0]].clone(), self.names[seats[1]].clone()];
+        let players = [
+            Player::new(names[0].clone(), 1, standard_set()),
+            Player::new(names[1].clone(), 2, standard_set()),
+        ];
+        let mut session =
+            Session::new(players).with_rules(Rules::default().with_moves_per_round(self.mov_num));
+        let mut record = GameRecord::of(&session);
+        while session.is_active() && record.len() < self.max_rounds {
+            let map = session.get_last_map();
+            let moves = [
+                self.bots[seats[0]].choose_moves(&map, &session.players()[0], self.mov_num),
+                self.bots[seats[1]].choose_moves(&map, &session.players()[1], self.mov_num),
+            ];
+            record.push(&moves);
+            session.play_round(moves);
+        }
+        record.finish(session.result());
+        (session, record)
+    }
+}
+
+/// Elo update after a game where the first contestant scored `score`.
+fn update_ratings(ratings: [f64; 2], score: f64) -> [f64; 2] {
+    let expected = 1.0 / (1.0 + 10f64.powf((ratings[1] - ratings[0]) / 400.0));
+    let delta = K_FACTOR * (score - expected);
+    [ratings[0] + delta, ratings[1] - delta]
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::bot::RandomBot;
+    use crate::record::GameRecord;
+
+    #[test]
+    fn test_elo_update() {
+        let [a, b] = update_ratings([1500.0, 1500.0], 1.0);
+        assert_eq!(a, 1516.0);
+        assert_eq!(b, 1484.0);
+        let [a, b] = update_ratings([1500.0, 1500.0], 0.5);
+        assert_eq!((a, b), (1500.0, 1500.0));
+    }
+
+    #[test]
+    fn test_run_writes_records() {
+        let dir = std::env::temp_dir().join(format!("not_so_chess_{}", std::process::id()));
+        let mut tournament = Tournament::new(
+            ["a".to_string(), "b".to_string()],
+            [Box::new(RandomBot::new(1)), Box::new(RandomBot::new(2))],
+        )
+        .with_out_dir(dir.clone());
+        let report = tournament.run(4).unwrap();
+        assert_eq!(report.games(), 4);
+        assert!(report.average_length() > 0.0);
+        let ratings: f64 = report.ratings.iter().sum();
+        assert!((ratings - 2.0 * START_RATING).abs() < 1e-6);
+
+        let second = GameRecord::load(dir.join("game_0002.txt")).unwrap();
+        assert_eq!(second.names(), &["b".to_string(), "a".to_string()]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
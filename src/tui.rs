@@ -0,0 +1,442 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::input::Timed;
+use crate::render::Renderer;
+use crate::{Entity, Map, Moves, Player, Position};
+
+/// How many lines of the round log are shown next to the board.
+const LOG_LINES: usize = 10;
+
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const CURSOR: &str = "\x1b[7m";
+const REACHABLE: &str = "\x1b[42m";
+const QUEUED: &str = "\x1b[43m";
+const RESET: &str = "\x1b[0m";
+
+/// A key press the move picker understands.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Select the entity under the cursor or queue a move to the cursor.
+    Select,
+    /// Drop the selection.
+    Cancel,
+    /// Remove the last queued move.
+    Undo,
+    /// Hand in the queued moves.
+    Submit,
+    /// Give up the game.
+    Quit,
+    Other,
+}
+
+impl Key {
+    /// Read one key press, arrow keys arrive as escape sequences.
+    pub fn read<R: Read>(input: &mut R) -> io::Result<Key> {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        Ok(match byte[0] {
+            0x1b => {
+                let mut sequence = [0; 2];
+                input.read_exact(&mut sequence)?;
+                match sequence {
+                    [b'[', b'A'] => Key::Up,
+                    [b'[', b'B'] => Key::Down,
+                    [b'[', b'C'] => Key::Right,
+                    [b'[', b'D'] => Key::Left,
+                    _ => Key::Other,
+                }
+            }
+            b'k' => Key::Up,
+            b'j' => Key::Down,
+            b'l' => Key::Right,
+            b'h' => Key::Left,
+            b' ' | b'\r' | b'\n' => Key::Select,
+            b'x' => Key::Cancel,
+            b'u' | 0x7f | 0x08 => Key::Undo,
+            b's' => Key::Submit,
+            // Raw mode hands over Ctrl-C as a byte instead of a signal.
+            b'q' | 0x03 => Key::Quit,
+            _ => Key::Other,
+        })
+    }
+}
+
+/// State of one player picking their moves for a round.
+#[derive(Debug, Clone)]
+pub struct Picker {
+    owner: u128,
+    mov_num: usize,
+    cursor: Position,
+    selected: Option<Entity>,
+    queued: Vec<(Entity, Position)>,
+    done: bool,
+    quit: bool,
+}
+
+impl Picker {
+    /// Start with the cursor on the player's first entity.
+    pub fn new(map: &Map, player: &Player, mov_num: usize) -> Self {
+        let cursor = map
+            .entities()
+            .find(|e| e.owner_id == player.id)
+            .map_or(Position::new(0, 0), |e| e.position);
+        Self {
+            owner: player.id,
+            mov_num,
+            cursor,
+            selected: None,
+            queued: vec![],
+            done: false,
+            quit: false,
+        }
+    }
+
+    /// Apply a key press, returns whether the moves are handed in. Moves are
+    /// checked against `map` with the queued ones played, as the engine
+    /// applies them one after another.
+    pub fn handle(&mut self, key: Key, map: &Map) -> bool {
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        match key {
+            Key::Up => self.cursor.y = (y + 1).min(15),
+            Key::Down => self.cursor.y = y.saturating_sub(1),
+            Key::Right => self.cursor.x = (x + 1).min(15),
+            Key::Left => self.cursor.x = x.saturating_sub(1),
+            Key::Select => match self.selected.take() {
+                Some(entity) if self.board(map).is_legal(&entity, self.cursor) => {
+                    self.queued.push((entity, self.cursor));
+                    self.done = self.queued.len() >= self.mov_num;
+                }
+                _ => self.selected = self.selectable(map),
+            },
+            Key::Cancel => self.selected = None,
+            Key::Undo => {
+                self.queued.pop();
+            }
+            Key::Submit => self.done = true,
+            Key::Quit => {
+                self.quit = true;
+                self.done = true;
+            }
+            Key::Other => {}
+        }
+        self.done
+    }
+
+    /// The player's entity under the cursor, unless it already has a move.
+    fn selectable(&self, map: &Map) -> Option<Entity> {
+        map.entities()
+            .find(|e| e.position == self.cursor && e.owner_id == self.owner)
+            .filter(|e| self.queued.iter().all(|(q, _)| q.position != e.position))
+            .cloned()
+    }
+
+    /// Squares the selected entity may move to after the queued moves.
+    pub fn reachable(&self, map: &Map) -> Vec<Position> {
+        match &self.selected {
+            Some(entity) => self.board(map).legal_moves(entity),
+            None => vec![],
+        }
+    }
+
+    /// The map once the queued moves are played.
+    fn board(&self, map: &Map) -> Map {
+        map.apply_moves(&self.moves(), self.owner)
+    }
+
+    /// The queued moves as the session takes them.
+    pub fn moves(&self) -> Moves {
+        let mut moves: Moves = [None, None, None];
+        for (slot, queued) in moves.iter_mut().zip(self.queued.iter()) {
+            *slot = Some(queued.clone());
+        }
+        moves
+    }
+}
+
+/// Full-screen move entry for human players, drawing the board with a cursor
+/// next to the entity under it, the queued moves and the round log.
+pub struct Tui {
+    log: Arc<Mutex<Vec<String>>>,
+    renderer: Renderer,
+}
+
+impl Tui {
+    /// `log` is filled by whoever watches the session, usually an observer.
+    pub fn new(log: Arc<Mutex<Vec<String>>>) -> Self {
+        Self {
+            log,
+            renderer: Renderer::for_stdout(),
+        }
+    }
+
+    /// Let `player` pick up to `mov_num` moves with the keyboard until
+    /// `deadline`, `None` if they give up the game.
+    pub fn read_moves(
+        &mut self,
+        map: &Map,
+        player: &Player,
+        mov_num: usize,
+        deadline: Option<Instant>,
+    ) -> Option<Moves> {
+        // Without a terminal to switch keys still arrive, line by line.
+        let raw = RawMode::enable().ok();
+        let mut picker = Picker::new(map, player, mov_num);
+        let mut input = Timed::stdin(deadline);
+        loop {
+            let screen = render(
+                &picker,
+                map,
+                player,
+                &self.log.lock().unwrap(),
+                self.renderer,
+            );
+            print!("{}{}", CLEAR, screen.join("\r\n"));
+            let _ = io::stdout().flush();
+            match Key::read(&mut input) {
+                Ok(key) if !picker.handle(key, map) => {}
+                _ => break,
+            }
+        }
+        drop(raw);
+        println!();
+        match picker.quit {
+            true => None,
+            false => Some(picker.moves()),
+        }
+    }
+}
+
+/// Raw terminal input until dropped, switched through `stty`. Holds the
+/// settings from before to restore them.
+struct RawMode(String);
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode(saved.trim().to_string()))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.0]);
+    }
+}
+
+/// Run `stty` on the terminal, returns what it printed.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::other("stty failed"))
+    }
+}
+
+/// Lines of the screen: the board on the left, the entity under the cursor,
+/// the queued moves and the end of `log` on the right.
+pub fn render(
+    picker: &Picker,
+    map: &Map,
+    player: &Player,
+    log: &[String],
+    renderer: Renderer,
+) -> Vec<String> {
+    let reachable = picker.reachable(map);
+    let mut board = vec![];
+    for y in (0..16).rev() {
+        let mut line = format!("{:X} ", y);
+        for x in 0..16 {
+            let square = Position::new(x, y);
+            let cell = renderer.square(map, square);
+            let style = if square == picker.cursor {
+                CURSOR
+            } else if reachable.contains(&square) {
+                REACHABLE
+            } else if picker.queued.iter().any(|(e, _)| e.position == square) {
+                QUEUED
+            } else {
+                ""
+            };
+            match style {
+                "" => line.push_str(&cell),
+                style => line.push_str(&format!("{}{}{}", style, cell, RESET)),
+            }
+        }
+        board.push(line);
+    }
+    board.push("  0 1 2 3 4 5 6 7 8 9 A B C D E F".to_string());
+
+    let mut panel = vec![
+        format!(
+            "{} to move, {} left",
+            player.name(),
+            picker.mov_num - picker.queued.len()
+        ),
+        format!("Cursor {}", picker.cursor),
+    ];
+    match map.entities().find(|e| e.position == picker.cursor) {
+        Some(e) => {
+            panel.push(format!(
+                "{} of P{}, {}",
+                e.figure.name(),
+                e.owner_id,
+                e.figure.fig_type()
+            ));
+            panel.push(format!("{} points", e.points));
+        }
+        None => panel.push(format!("Empty {} square", map.terrain(picker.cursor))),
+    }
+    panel.push(String::new());
+    panel.push("Queued:".to_string());
+    for (e, target) in picker.queued.iter() {
+        panel.push(format!(
+            "  {} {} -> {}",
+            e.figure.symbol(),
+            e.position,
+            target
+        ));
+    }
+    panel.push(String::new());
+    panel.push("Log:".to_string());
+    for line in log.iter().skip(log.len().saturating_sub(LOG_LINES)) {
+        panel.push(format!("  {}", line));
+    }
+
+    let mut screen: Vec<String> = board
+        .into_iter()
+        .zip(
+            panel
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::repeat("")),
+        )
+        .map(|(board, panel)| format!("{}  {}", board, panel).trim_end().to_string())
+        .collect();
+    for line in panel.iter().skip(screen.len()) {
+        screen.push(format!("{:36}{}", "", line));
+    }
+    screen.push(
+        "arrows/hjkl move, space select or queue, x cancel, u undo, s submit, q quit".to_string(),
+    );
+    screen
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::figures::pawn;
+    use crate::testing::{place, players};
+
+    #[test]
+    fn test_read_keys() {
+        let mut input: &[u8] = b"\x1b[A\x1b[Dj s\x03q";
+        let mut keys = vec![];
+        while let Ok(key) = Key::read(&mut input) {
+            keys.push(key);
+        }
+        assert_eq!(
+            keys,
+            vec![
+                Key::Up,
+                Key::Left,
+                Key::Down,
+                Key::Select,
+                Key::Submit,
+                Key::Quit,
+                Key::Quit
+            ]
+        );
+    }
+
+    #[test]
+    fn test_queue_moves() {
+        let [ame, gura] = players();
+        let map = Map::init(ame.clone(), gura);
+        let mut picker = Picker::new(&map, &ame, 2);
+        picker.cursor = Position::new(0, 2);
+        assert!(!picker.handle(Key::Select, &map));
+        assert!(picker.reachable(&map).contains(&Position::new(0, 3)));
+        // An illegal target keeps nothing queued.
+        picker.cursor = Position::new(9, 9);
+        picker.handle(Key::Select, &map);
+        assert!(picker.queued.is_empty());
+
+        picker.cursor = Position::new(0, 2);
+        picker.handle(Key::Select, &map);
+        picker.handle(Key::Up, &map);
+        assert!(!picker.handle(Key::Select, &map));
+        assert_eq!(picker.queued[0].1, Position::new(0, 3));
+        // An entity is moved once per round.
+        picker.handle(Key::Down, &map);
+        picker.handle(Key::Select, &map);
+        assert!(picker.selected.is_none());
+
+        picker.handle(Key::Undo, &map);
+        assert_eq!(picker.moves(), [None, None, None]);
+        picker.handle(Key::Select, &map);
+        picker.handle(Key::Up, &map);
+        picker.handle(Key::Select, &map);
+        assert!(picker.handle(Key::Submit, &map));
+        assert!(picker.moves()[0].is_some());
+        assert!(!picker.quit);
+
+        let mut quitter = Picker::new(&map, &ame, 2);
+        assert!(quitter.handle(Key::Quit, &map) && quitter.quit);
+    }
+
+    #[test]
+    fn test_queued_moves_change_the_board() {
+        let mut map = Map::empty();
+        place(&mut map, 1, pawn(), 5, 5);
+        place(&mut map, 1, pawn(), 5, 7);
+        place(&mut map, 1, pawn(), 7, 5);
+        let mut picker = Picker::new(&map, &players()[0], 3);
+        let queue = |picker: &mut Picker, from: Position, to: Position| {
+            picker.cursor = from;
+            picker.handle(Key::Select, &map);
+            picker.cursor = to;
+            picker.handle(Key::Select, &map);
+        };
+        queue(&mut picker, Position::new(5, 5), Position::new(6, 5));
+        // The square the first pawn left is free for the second one.
+        queue(&mut picker, Position::new(5, 7), Position::new(5, 5));
+        assert_eq!(picker.queued.len(), 2);
+        // The square the first pawn went to is not.
+        picker.cursor = Position::new(7, 5);
+        picker.handle(Key::Select, &map);
+        assert!(!picker.reachable(&map).contains(&Position::new(6, 5)));
+        picker.cursor = Position::new(6, 5);
+        picker.handle(Key::Select, &map);
+        assert_eq!(picker.queued.len(), 2);
+    }
+
+    #[test]
+    fn test_render_highlights_reachable() {
+        let [ame, gura] = players();
+        let map = Map::init(ame.clone(), gura);
+        let mut picker = Picker::new(&map, &ame, 1);
+        picker.cursor = Position::new(0, 2);
+        picker.handle(Key::Select, &map);
+        let log = vec!["P1: moved".to_string()];
+        let screen = render(&picker, &map, &ame, &log, Renderer::plain());
+        // Row 3 is the fourth line from the bottom of the board.
+        assert!(screen[12].starts_with(&format!("3 {}_ {}", REACHABLE, RESET)));
+        assert!(screen[13].starts_with(&format!("2 {}P {}", CURSOR, RESET)));
+        assert!(screen[0].ends_with("Ame to move, 1 left"));
+        assert!(screen.iter().any(|line| line.ends_with("  P1: moved")));
+    }
+}